@@ -0,0 +1,305 @@
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{mpsc, Arc, OnceLock};
+
+use crate::source::{DirSource, Entries, FsSource};
+use crate::{FileType, LinkTarget};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+    TarXz,
+    TarZst,
+}
+
+impl Kind {
+    fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        let has_extension = |extensions: &[&str]| extensions.iter().any(|ext| name.ends_with(ext));
+        let kind = if has_extension(&[".zip", ".whl", ".jar"]) {
+            Kind::Zip
+        } else if has_extension(&[".tar.gz", ".tgz", ".crate"]) {
+            Kind::TarGz
+        } else if has_extension(&[".tar.xz", ".txz"]) {
+            Kind::TarXz
+        } else if has_extension(&[".tar.zst", ".tzst"]) {
+            Kind::TarZst
+        } else if has_extension(&[".tar"]) {
+            Kind::Tar
+        } else {
+            return None;
+        };
+        Some(kind)
+    }
+}
+
+/// A directory inside a zip or tar archive, its top level by default.
+///
+/// Tar archives are read as a stream, so a preview stops reading once it runs
+/// out of time. Zip archives list their contents at the end, which is read at once.
+///
+/// A directory named like an archive is listed as a plain directory. That's
+/// only found out when listing, since a `stat` can hang on a stale mount.
+#[derive(Debug, Clone)]
+pub struct ArchiveSource {
+    archive: PathBuf,
+    kind: Kind,
+    /// Components of the directory inside the archive.
+    path: Vec<String>,
+    /// The directory listed instead, if `archive` is one.
+    directory: OnceLock<Option<FsSource>>,
+}
+
+impl ArchiveSource {
+    /// `None` unless `archive` is named like a zip, tar, tar.gz, tar.xz or
+    /// tar.zst archive, including `.crate`, `.whl` and `.jar` files.
+    pub fn new(archive: impl Into<PathBuf>) -> Option<Self> {
+        let archive = archive.into();
+        let kind = Kind::from_path(&archive)?;
+        Some(ArchiveSource {
+            archive,
+            kind,
+            path: vec![],
+            directory: OnceLock::new(),
+        })
+    }
+
+    /// List the directory at `path` inside the archive instead.
+    pub fn path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = normalize(&path.as_ref().to_string_lossy());
+        self
+    }
+
+    fn directory(&self) -> Option<&FsSource> {
+        self.directory
+            .get_or_init(|| {
+                let is_dir = fs::metadata(&self.archive).is_ok_and(|metadata| metadata.is_dir());
+                let directory = self
+                    .path
+                    .iter()
+                    .fold(self.archive.clone(), |directory, name| directory.join(name));
+                is_dir.then(|| FsSource::new(directory))
+            })
+            .as_ref()
+    }
+
+    fn tar_entries(&self, reader: Box<dyn Read + Send>) -> Entries<'_> {
+        let headers = spawn_tar_headers(reader);
+        let mut seen = HashSet::new();
+        Box::new(std::iter::from_fn(move || loop {
+            let (path, file_type) = match headers.recv().ok()? {
+                Ok(header) => header,
+                Err(err) => return Some(Err(err)),
+            };
+            if let Some(entry) = self.top_level(&path, file_type, &mut seen) {
+                return Some(Ok(entry));
+            }
+        }))
+    }
+
+    fn zip_entries(&self) -> io::Result<Entries<'_>> {
+        let mut zip = zip::ZipArchive::new(File::open(&self.archive)?).map_err(io::Error::other)?;
+        let mut seen = HashSet::new();
+        let mut entries = vec![];
+        for index in 0..zip.len() {
+            let file = zip.by_index_raw(index).map_err(io::Error::other)?;
+            let file_type = if file.is_dir() {
+                FileType::Dir
+            } else if file.is_symlink() {
+                FileType::Symlink
+            } else {
+                FileType::File
+            };
+            let name = file.name().map_err(io::Error::other)?;
+            entries.extend(self.top_level(&name, file_type, &mut seen).map(Ok));
+        }
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    /// The entry of the listed directory that `path` is in, unless already seen.
+    fn top_level(
+        &self,
+        path: &str,
+        file_type: FileType,
+        seen: &mut HashSet<String>,
+    ) -> Option<(OsString, FileType)> {
+        let components = normalize(path);
+        let relative = components.strip_prefix(self.path.as_slice())?;
+        let (name, rest) = relative.split_first()?;
+        // Archives don't always have entries for the directories in between
+        let file_type = if rest.is_empty() {
+            file_type
+        } else {
+            FileType::Dir
+        };
+        seen.insert(name.clone())
+            .then(|| (OsString::from(name), file_type))
+    }
+}
+
+impl DirSource for ArchiveSource {
+    fn entries(&self) -> io::Result<Entries<'_>> {
+        if let Some(directory) = self.directory() {
+            return directory.entries();
+        }
+        let file = BufReader::new(File::open(&self.archive)?);
+        let reader: Box<dyn Read + Send> = match self.kind {
+            Kind::Zip => return self.zip_entries(),
+            Kind::Tar => Box::new(file),
+            Kind::TarGz => Box::new(flate2::bufread::MultiGzDecoder::new(file)),
+            Kind::TarXz => Box::new(xz2::bufread::XzDecoder::new(file)),
+            Kind::TarZst => Box::new(zstd::stream::read::Decoder::with_buffer(file)?),
+        };
+        Ok(self.tar_entries(reader))
+    }
+
+    fn metadata(&self, name: &OsStr) -> io::Result<Option<fs::Metadata>> {
+        match self.directory() {
+            Some(directory) => directory.metadata(name),
+            None => Ok(None),
+        }
+    }
+
+    fn link_target(&self, name: &OsStr) -> Option<LinkTarget> {
+        self.directory()?.link_target(name)
+    }
+
+    fn read_link(&self, name: &OsStr) -> Option<PathBuf> {
+        self.directory()?.read_link(name)
+    }
+
+    fn open(&self, name: &OsStr) -> io::Result<Arc<dyn DirSource>> {
+        if let Some(directory) = self.directory() {
+            return directory.open(name);
+        }
+        let mut source = self.clone();
+        source.path.push(name.to_string_lossy().into_owned());
+        source.directory = OnceLock::new();
+        Ok(Arc::new(source))
+    }
+}
+
+/// Components of a path inside an archive, without `.`, leading `/` or `./`.
+fn normalize(path: &str) -> Vec<String> {
+    Path::new(path)
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Read the paths and types of the entries of a tar stream on their own
+/// thread, since `tar::Entries` borrows the archive it reads from. The thread
+/// stops once the receiver is dropped, so only as much is read as is listed.
+fn spawn_tar_headers(
+    reader: Box<dyn Read + Send>,
+) -> mpsc::Receiver<io::Result<(String, FileType)>> {
+    let (sender, receiver) = mpsc::sync_channel(64);
+    std::thread::spawn(move || {
+        let mut archive = tar::Archive::new(reader);
+        let entries = match archive.entries() {
+            Ok(entries) => entries,
+            Err(err) => {
+                let _ = sender.send(Err(err));
+                return;
+            }
+        };
+        for entry in entries {
+            // Global pax headers hold defaults for the rest, not an entry
+            if let Ok(entry) = &entry {
+                if entry.header().entry_type().is_pax_global_extensions() {
+                    continue;
+                }
+            }
+            let header = entry.and_then(|entry| {
+                let entry_type = entry.header().entry_type();
+                let file_type = if entry_type.is_dir() {
+                    FileType::Dir
+                } else if entry_type.is_symlink() {
+                    FileType::Symlink
+                } else if entry_type.is_character_special() {
+                    FileType::CharDevice
+                } else if entry_type.is_block_special() {
+                    FileType::BlockDevice
+                } else if entry_type.is_fifo() {
+                    FileType::Fifo
+                } else {
+                    FileType::File
+                };
+                Ok((entry.path()?.to_string_lossy().into_owned(), file_type))
+            });
+            let failed = header.is_err();
+            if sender.send(header).is_err() || failed {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_and_pax_paths_are_listed() {
+        let long_dir = "a-directory-name-long-enough-that-paths-below-it-overflow-the-name-field";
+        let mut builder = tar::Builder::new(vec![]);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(0);
+        builder
+            .append_data(
+                &mut header,
+                format!("{long_dir}/{long_dir}/file"),
+                io::empty(),
+            )
+            .unwrap();
+        builder
+            .append_pax_extensions([("path", format!("pax/{long_dir}/{long_dir}").as_bytes())])
+            .unwrap();
+        let mut header = tar::Header::new_ustar();
+        header.set_path("truncated").unwrap();
+        header.set_size(0);
+        header.set_cksum();
+        builder.append(&header, io::empty()).unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let source = ArchiveSource::new("test.tar").unwrap();
+        let entries: Vec<_> = source
+            .tar_entries(Box::new(io::Cursor::new(archive)))
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            entries,
+            [
+                (OsString::from(long_dir), FileType::Dir),
+                (OsString::from("pax"), FileType::Dir),
+            ]
+        );
+    }
+
+    #[test]
+    fn directories_named_like_archives_are_listed() {
+        let directory = std::env::temp_dir().join(format!("ls-preview-{}.zip", std::process::id()));
+        fs::create_dir_all(directory.join("sub")).unwrap();
+        File::create(directory.join("sub").join("file")).unwrap();
+
+        let source = ArchiveSource::new(&directory).unwrap().path("sub");
+        let entries: Vec<_> = source
+            .entries()
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(entries, [(OsString::from("file"), FileType::File)]);
+        assert!(source.metadata(OsStr::new("file")).unwrap().is_some());
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use crate::store::KeyedFile;
+use crate::FileType;
+
+/// A refresh holding the lock longer than this is assumed to have died.
+const STALE_LOCK: Duration = Duration::from_secs(60);
+
+/// List all of `directory` without a time limit and store it in the cache.
+///
+/// Meant to run in the background after a preview ran out of time, so the
+/// next preview of the directory is complete. Does nothing if another
+/// refresh of the same directory is running.
+pub fn refresh_cache(cache_dir: &Path, directory: &Path) -> io::Result<()> {
+    let Some(cache) = Cache::open(cache_dir, directory) else {
+        return Ok(());
+    };
+    if cache.read().is_some() {
+        return Ok(());
+    }
+    fs::create_dir_all(cache_dir)?;
+    let lock_path = cache.file.path().with_extension("lock");
+    if lock_is_stale(&lock_path) {
+        let _ = fs::remove_file(&lock_path);
+    }
+    if fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&lock_path)
+        .is_err()
+    {
+        return Ok(());
+    }
+    let entries = fs::read_dir(directory).and_then(|read_dir| {
+        read_dir
+            .map(|entry| {
+                let entry = entry?;
+                Ok((entry.file_name(), FileType::from(entry.file_type()?)))
+            })
+            .collect::<io::Result<Vec<_>>>()
+    });
+    let result = entries.and_then(|entries| cache.write(&entries));
+    let _ = fs::remove_file(&lock_path);
+    result
+}
+
+fn lock_is_stale(lock_path: &Path) -> bool {
+    fs::metadata(lock_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > STALE_LOCK)
+}
+
+/// The cached listing of a directory, keyed by device and inode and valid
+/// while the directory's mtime doesn't change.
+pub(crate) struct Cache {
+    file: KeyedFile,
+    mtime: (i64, i64),
+}
+
+impl Cache {
+    pub(crate) fn open(cache_dir: &Path, directory: &Path) -> Option<Self> {
+        let metadata = fs::metadata(directory).ok()?;
+        Some(Cache {
+            file: KeyedFile::new(cache_dir, "cache", &metadata),
+            mtime: (metadata.mtime(), metadata.mtime_nsec()),
+        })
+    }
+
+    /// The cached entries, `None` if missing or stale.
+    pub(crate) fn read(&self) -> Option<Vec<(OsString, FileType)>> {
+        let contents = self.file.read()?;
+        let newline = contents.iter().position(|&byte| byte == b'\n')?;
+        let mtime = std::str::from_utf8(&contents[..newline]).ok()?;
+        if mtime != format!("{} {}", self.mtime.0, self.mtime.1) {
+            return None;
+        }
+        contents[newline + 1..]
+            .split(|&byte| byte == 0)
+            .filter(|record| !record.is_empty())
+            .map(|record| {
+                let file_type = from_code(record[0])?;
+                Some((OsString::from_vec(record[1..].to_vec()), file_type))
+            })
+            .collect()
+    }
+
+    /// Store a complete listing, replacing the old one atomically.
+    pub(crate) fn write(&self, entries: &[(OsString, FileType)]) -> io::Result<()> {
+        self.file.write(|file| {
+            writeln!(file, "{} {}", self.mtime.0, self.mtime.1)?;
+            for (name, file_type) in entries {
+                file.write_all(&[code(*file_type)])?;
+                file.write_all(name.as_bytes())?;
+                file.write_all(&[0])?;
+            }
+            Ok(())
+        })
+    }
+}
+
+fn code(file_type: FileType) -> u8 {
+    match file_type {
+        FileType::Dir => b'd',
+        FileType::File => b'f',
+        FileType::Symlink => b'l',
+        FileType::Socket => b's',
+        FileType::Fifo => b'p',
+        FileType::BlockDevice => b'b',
+        FileType::CharDevice => b'c',
+        FileType::Unknown => b'?',
+    }
+}
+
+fn from_code(code: u8) -> Option<FileType> {
+    Some(match code {
+        b'd' => FileType::Dir,
+        b'f' => FileType::File,
+        b'l' => FileType::Symlink,
+        b's' => FileType::Socket,
+        b'p' => FileType::Fifo,
+        b'b' => FileType::BlockDevice,
+        b'c' => FileType::CharDevice,
+        b'?' => FileType::Unknown,
+        _ => return None,
+    })
+}
@@ -0,0 +1,202 @@
+use std::env;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+/// Command line arguments with defaults applied in front of them.
+///
+/// Later arguments override earlier ones, so the order is: the config file,
+/// the selected profile from it, `LS_PREVIEW_OPTS`, then the actual arguments.
+pub fn args() -> Result<Vec<OsString>, String> {
+    let mut cli = env::args_os();
+    let program = cli.next().unwrap_or_else(|| "ls-preview".into());
+    let cli: Vec<OsString> = cli.collect();
+
+    let env_opts: Vec<OsString> = match env::var("LS_PREVIEW_OPTS") {
+        Ok(opts) => shlex::split(&opts)
+            .ok_or("LS_PREVIEW_OPTS: unbalanced quotes")?
+            .into_iter()
+            .map(OsString::from)
+            .collect(),
+        Err(_) => vec![],
+    };
+
+    with_config(program, read_config()?, env_opts, cli)
+}
+
+/// `cli` with the defaults from `config` and `env_opts` in front of it.
+fn with_config(
+    program: OsString,
+    mut config: Table,
+    env_opts: Vec<OsString>,
+    cli: Vec<OsString>,
+) -> Result<Vec<OsString>, String> {
+    let profiles = match config.remove("profile") {
+        Some(Value::Table(profiles)) => profiles,
+        Some(_) => return Err("config: `profile` must be a table of profiles".to_string()),
+        None => Table::new(),
+    };
+
+    let mut args = vec![program];
+    args.extend(table_to_args(&config)?);
+    if let Some(name) = selected_profile(env_opts.iter().chain(&cli)) {
+        match profiles.get(&name) {
+            Some(Value::Table(profile)) => args.extend(table_to_args(profile)?),
+            _ => return Err(format!("unknown profile `{name}`")),
+        }
+    }
+    args.extend(env_opts);
+    args.extend(cli);
+    Ok(args)
+}
+
+/// `$XDG_CONFIG_HOME/ls-preview/config.toml`, or under `~/.config`.
+fn config_path() -> Option<PathBuf> {
+    Some(ls_preview::default_config_dir()?.join("config.toml"))
+}
+
+fn read_config() -> Result<Table, String> {
+    let Some(path) = config_path() else {
+        return Ok(Table::new());
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => contents
+            .parse()
+            .map_err(|err| format!("{}: {err}", path.display())),
+        Err(_) => Ok(Table::new()),
+    }
+}
+
+/// The last `--profile` given.
+fn selected_profile<'a>(args: impl Iterator<Item = &'a OsString>) -> Option<String> {
+    let mut profile = None;
+    let mut args = args.map(|arg| arg.to_string_lossy());
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        } else if arg == "--profile" {
+            profile = args.next().map(|name| name.into_owned());
+        } else if let Some(name) = arg.strip_prefix("--profile=") {
+            profile = Some(name.to_string());
+        }
+    }
+    profile
+}
+
+/// Turn `sort = "natural"` into `--sort natural`, `dirs_first = true` into
+/// `--dirs-first` and `dirs_first = false` into `--no-dirs-first`, so a
+/// profile can turn off what the top level turned on.
+fn table_to_args(table: &Table) -> Result<Vec<OsString>, String> {
+    let mut args = vec![];
+    for (key, value) in table {
+        let flag = format!("--{}", key.replace('_', "-"));
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                Value::Boolean(true) => args.push(flag.clone().into()),
+                Value::Boolean(false) => args.push(negated(key).into()),
+                Value::String(value) => args.extend([flag.clone().into(), value.into()]),
+                Value::Integer(value) => {
+                    args.extend([flag.clone().into(), value.to_string().into()])
+                }
+                Value::Float(value) => args.extend([flag.clone().into(), value.to_string().into()]),
+                _ => return Err(format!("config: unsupported value for `{key}`")),
+            }
+        }
+    }
+    Ok(args)
+}
+
+/// The flag undoing the switch `key`, `--git` for `no_git`.
+fn negated(key: &str) -> String {
+    let key = key.replace('_', "-");
+    match key.strip_prefix("no-") {
+        Some(key) => format!("--{key}"),
+        None => format!("--no-{key}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args_from(config: &str) -> Result<Vec<String>, String> {
+        let table: Table = config.parse().unwrap();
+        let args = table_to_args(&table)?;
+        Ok(args
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect())
+    }
+
+    #[test]
+    fn booleans_become_bare_flags() {
+        assert_eq!(
+            args_from("dirs_first = true\nicons = false\nno_git = false").unwrap(),
+            ["--dirs-first", "--no-icons", "--git"]
+        );
+    }
+
+    #[test]
+    fn values_follow_their_flag() {
+        assert_eq!(
+            args_from("max_lines = 3\nsort = \"natural\"").unwrap(),
+            ["--max-lines", "3", "--sort", "natural"]
+        );
+    }
+
+    #[test]
+    fn arrays_repeat_the_flag() {
+        assert_eq!(
+            args_from("exclude = [\"*.pyc\", \"target\"]").unwrap(),
+            ["--exclude", "*.pyc", "--exclude", "target"]
+        );
+        assert_eq!(args_from("include = []").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn tables_are_rejected() {
+        assert_eq!(
+            args_from("[colors]\ndi = 1").unwrap_err(),
+            "config: unsupported value for `colors`"
+        );
+    }
+
+    #[test]
+    fn profiles_turn_off_switches_turned_on_above() {
+        use clap::Parser;
+
+        let config = "icons = true\nno_git = true\n\
+                      [profile.fzf]\nicons = false\nno_git = false";
+        let args = |cli: &[&str]| {
+            let cli = cli.iter().map(OsString::from).collect();
+            let args = with_config("ls-preview".into(), config.parse().unwrap(), vec![], cli);
+            crate::Args::try_parse_from(args.unwrap()).unwrap()
+        };
+        let top_level = args(&[]);
+        assert!(top_level.icons && top_level.no_git);
+        let profile = args(&["--profile", "fzf"]);
+        assert!(!profile.icons && !profile.no_git);
+        let cli = args(&["--profile", "fzf", "--icons"]);
+        assert!(cli.icons);
+    }
+
+    #[test]
+    fn last_profile_flag_wins() {
+        let args: Vec<OsString> = [
+            "--profile",
+            "fzf",
+            "--profile=prompt",
+            "--",
+            "--profile",
+            "x",
+        ]
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+        assert_eq!(selected_profile(args.iter()).as_deref(), Some("prompt"));
+    }
+}
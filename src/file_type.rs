@@ -0,0 +1,82 @@
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+
+/// Type of an entry, like [`fs::FileType`] but also for entries that don't
+/// come straight from the filesystem, such as cached ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    Dir,
+    File,
+    Symlink,
+    Socket,
+    Fifo,
+    BlockDevice,
+    CharDevice,
+    Unknown,
+}
+
+impl FileType {
+    pub fn is_dir(self) -> bool {
+        self == FileType::Dir
+    }
+
+    pub fn is_file(self) -> bool {
+        self == FileType::File
+    }
+
+    pub fn is_symlink(self) -> bool {
+        self == FileType::Symlink
+    }
+
+    pub fn is_socket(self) -> bool {
+        self == FileType::Socket
+    }
+
+    pub fn is_fifo(self) -> bool {
+        self == FileType::Fifo
+    }
+
+    pub fn is_block_device(self) -> bool {
+        self == FileType::BlockDevice
+    }
+
+    pub fn is_char_device(self) -> bool {
+        self == FileType::CharDevice
+    }
+
+    /// Lowercase name, e.g. for machine-readable output.
+    pub fn name(self) -> &'static str {
+        match self {
+            FileType::Dir => "directory",
+            FileType::File => "file",
+            FileType::Symlink => "symlink",
+            FileType::Socket => "socket",
+            FileType::Fifo => "fifo",
+            FileType::BlockDevice => "block_device",
+            FileType::CharDevice => "char_device",
+            FileType::Unknown => "unknown",
+        }
+    }
+}
+
+impl From<fs::FileType> for FileType {
+    fn from(file_type: fs::FileType) -> Self {
+        if file_type.is_dir() {
+            FileType::Dir
+        } else if file_type.is_symlink() {
+            FileType::Symlink
+        } else if file_type.is_socket() {
+            FileType::Socket
+        } else if file_type.is_fifo() {
+            FileType::Fifo
+        } else if file_type.is_block_device() {
+            FileType::BlockDevice
+        } else if file_type.is_char_device() {
+            FileType::CharDevice
+        } else if file_type.is_file() {
+            FileType::File
+        } else {
+            FileType::Unknown
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use regex::Regex;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Which entries to show by name: `--include`, `--exclude` and `--match`.
+#[derive(Debug, Clone, Default)]
+pub struct NameFilter {
+    /// An entry must match one of these, if any.
+    include: Option<GlobSet>,
+    /// An entry must match none of these.
+    exclude: Option<GlobSet>,
+    /// An entry must contain a match.
+    pattern: Option<Regex>,
+}
+
+impl NameFilter {
+    pub fn new(
+        include: &[String],
+        exclude: &[String],
+        pattern: Option<&str>,
+    ) -> Result<Self, String> {
+        let pattern = pattern
+            .map(Regex::new)
+            .transpose()
+            .map_err(|err| err.to_string())?;
+        Ok(NameFilter {
+            include: glob_set(include)?,
+            exclude: glob_set(exclude)?,
+            pattern,
+        })
+    }
+
+    pub fn is_match(&self, name: &OsStr) -> bool {
+        let name = name.to_string_lossy();
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.is_match(name.as_ref()))
+            && !self
+                .exclude
+                .as_ref()
+                .is_some_and(|exclude| exclude.is_match(name.as_ref()))
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(&name))
+    }
+}
+
+/// `None` without any globs.
+fn glob_set(globs: &[String]) -> Result<Option<GlobSet>, String> {
+    if globs.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|err| err.to_string())?);
+    }
+    builder.build().map(Some).map_err(|err| err.to_string())
+}
+
+/// Ignore files that apply to a directory, the way `fd` and `rg` read them.
+pub(crate) struct IgnoreRules {
+    directory: PathBuf,
+    /// Most specific first: `.ignore` before `.gitignore`, inner directories
+    /// before outer ones, then `.git/info/exclude` and the global excludes.
+    matchers: Vec<Gitignore>,
+}
+
+impl IgnoreRules {
+    pub(crate) fn new(directory: &Path) -> Self {
+        let directory = directory
+            .canonicalize()
+            .unwrap_or_else(|_| directory.to_path_buf());
+        let repo_root = directory
+            .ancestors()
+            .find(|ancestor| ancestor.join(".git").exists())
+            .map(Path::to_path_buf);
+
+        // `.gitignore` only applies inside a repository, `.ignore` applies anywhere
+        let ancestors: Vec<&Path> = match &repo_root {
+            Some(root) => directory
+                .ancestors()
+                .take_while(|ancestor| ancestor.starts_with(root))
+                .collect(),
+            None => vec![&directory],
+        };
+        let mut matchers = vec![];
+        for ancestor in ancestors {
+            matchers.push(Gitignore::new(ancestor.join(".ignore")).0);
+            if repo_root.is_some() {
+                matchers.push(Gitignore::new(ancestor.join(".gitignore")).0);
+            }
+        }
+        if let Some(root) = &repo_root {
+            // Patterns in `info/exclude` are relative to the work tree, not the file
+            let mut exclude = GitignoreBuilder::new(root);
+            exclude.add(root.join(".git/info/exclude"));
+            matchers.extend(exclude.build().ok());
+            matchers.push(GitignoreBuilder::new(root).build_global().0);
+        }
+        matchers.retain(|matcher| !matcher.is_empty());
+
+        IgnoreRules {
+            directory,
+            matchers,
+        }
+    }
+
+    pub(crate) fn is_ignored(&self, name: &OsStr, is_dir: bool) -> bool {
+        let path = self.directory.join(name);
+        self.matchers
+            .iter()
+            .map(|matcher| matcher.matched(&path, is_dir))
+            .find(|matched| !matched.is_none())
+            .is_some_and(|matched| matched.is_ignore())
+    }
+}
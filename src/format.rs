@@ -0,0 +1,173 @@
+use serde::Serialize;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+
+use crate::{get_entry_color_and_indicator, Entry, Listing};
+
+/// How a listing is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Format {
+    /// Styled columns
+    #[default]
+    Columns,
+    /// One JSON document per directory
+    Json,
+    /// One JSON object per line for each entry, then one for the directory
+    Ndjson,
+    /// Names of the shown entries, one per line
+    Lines,
+    /// Names of the shown entries, each followed by NUL
+    Print0,
+}
+
+#[derive(Serialize)]
+struct JsonListing {
+    directory: String,
+    dirs_only: bool,
+    timed_out: bool,
+    omitted: usize,
+    hidden: usize,
+    removed: usize,
+    entries: Vec<JsonEntry>,
+}
+
+#[derive(Serialize)]
+struct JsonEntry {
+    name: String,
+    /// The name's bytes when it isn't UTF-8, as `name` has U+FFFD in their place.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name_bytes: Option<Vec<u8>>,
+    #[serde(rename = "type")]
+    file_type: &'static str,
+    indicator: &'static str,
+    kept: bool,
+    new: bool,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum NdjsonRecord<'a> {
+    Entry {
+        directory: &'a str,
+        #[serde(flatten)]
+        entry: JsonEntry,
+    },
+    Summary {
+        directory: &'a str,
+        dirs_only: bool,
+        timed_out: bool,
+        omitted: usize,
+        hidden: usize,
+        removed: usize,
+    },
+}
+
+pub(crate) fn write_json<W: Write>(out: &mut W, listing: &Listing) -> io::Result<()> {
+    let json = JsonListing {
+        directory: listing.directory.to_string_lossy().into_owned(),
+        dirs_only: listing.dirs_only,
+        timed_out: listing.timed_out,
+        omitted: listing.omitted(),
+        hidden: listing.hidden,
+        removed: listing.removed,
+        entries: json_entries(listing).collect(),
+    };
+    serde_json::to_writer_pretty(&mut *out, &json)?;
+    writeln!(out)
+}
+
+pub(crate) fn write_ndjson<W: Write>(out: &mut W, listing: &Listing) -> io::Result<()> {
+    let directory = listing.directory.to_string_lossy();
+    for entry in json_entries(listing) {
+        let record = NdjsonRecord::Entry {
+            directory: &directory,
+            entry,
+        };
+        serde_json::to_writer(&mut *out, &record)?;
+        writeln!(out)?;
+    }
+    let summary = NdjsonRecord::Summary {
+        directory: &directory,
+        dirs_only: listing.dirs_only,
+        timed_out: listing.timed_out,
+        omitted: listing.omitted(),
+        hidden: listing.hidden,
+        removed: listing.removed,
+    };
+    serde_json::to_writer(&mut *out, &summary)?;
+    writeln!(out)
+}
+
+/// Write the names of the shown entries, unstyled, each followed by `terminator`.
+pub(crate) fn write_names<W: Write>(
+    out: &mut W,
+    listing: &Listing,
+    terminator: u8,
+) -> io::Result<()> {
+    for entry in &listing.entries[..listing.shown()] {
+        out.write_all(entry.name.as_bytes())?;
+        out.write_all(&[terminator])?;
+    }
+    Ok(())
+}
+
+/// Shown entries, then the ones cut off by the line limit, then the dropped ones.
+fn json_entries(listing: &Listing) -> impl Iterator<Item = JsonEntry> + '_ {
+    let shown = listing.shown();
+    listing
+        .entries
+        .iter()
+        .enumerate()
+        .map(move |(i, entry)| json_entry(entry, i < shown))
+        .chain(listing.dropped.iter().map(|entry| json_entry(entry, false)))
+}
+
+fn json_entry(entry: &Entry, kept: bool) -> JsonEntry {
+    JsonEntry {
+        name: entry.name.to_string_lossy().into_owned(),
+        name_bytes: entry
+            .name
+            .to_str()
+            .is_none()
+            .then(|| entry.name.as_bytes().to_vec()),
+        file_type: entry.file_type.name(),
+        indicator: get_entry_color_and_indicator(entry).1,
+        kept,
+        new: entry.is_new,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+    use crate::FileType;
+
+    fn json(name: &OsStr) -> serde_json::Value {
+        let entry = Entry {
+            name: name.to_owned(),
+            file_type: FileType::File,
+            width: 0,
+            shortened: None,
+            git_status: None,
+            is_new: false,
+            icon: None,
+            metadata: None,
+            link_target: None,
+            link_path: None,
+        };
+        serde_json::to_value(json_entry(&entry, true)).unwrap()
+    }
+
+    #[test]
+    fn names_that_arent_utf8_keep_their_bytes() {
+        let entry = json(OsStr::from_bytes(b"caf\xe9.txt"));
+        assert_eq!(entry["name"], "caf\u{fffd}.txt");
+        assert_eq!(entry["name_bytes"], serde_json::json!(b"caf\xe9.txt"));
+
+        let entry = json(OsStr::new("café.txt"));
+        assert_eq!(entry["name"], "café.txt");
+        assert!(entry.get("name_bytes").is_none());
+    }
+}
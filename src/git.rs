@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::time::Instant;
+
+use crate::list;
+
+/// Status of an entry in the enclosing git work tree.
+///
+/// Ordered by importance, directories show their most important status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GitStatus {
+    Ignored,
+    Untracked,
+    /// Only in the work tree, compared to a revision.
+    Added,
+    /// Only in a revision, compared to the work tree.
+    Deleted,
+    Staged,
+    Modified,
+    Conflicted,
+}
+
+/// A running `git status`, started alongside the listing.
+///
+/// It covers everything under the directory, so nested previews share it.
+#[derive(Debug)]
+pub(crate) struct PendingStatus {
+    directory: PathBuf,
+    child: Arc<Mutex<ChildState>>,
+    receiver: Mutex<mpsc::Receiver<StatusOutput>>,
+    output: OnceLock<StatusOutput>,
+}
+
+/// Porcelain output of `git status`, and where the directory is in the work tree.
+#[derive(Debug)]
+pub(crate) struct StatusOutput {
+    records: Vec<u8>,
+    prefix: PathBuf,
+}
+
+#[derive(Debug)]
+enum ChildState {
+    /// Still looking for the work tree.
+    NotStarted,
+    Running(Child),
+    /// Waited for, or given up on.
+    Finished,
+}
+
+impl PendingStatus {
+    /// Start `git status` for `directory` on a background thread, since even
+    /// finding the work tree can hang on a stale mount.
+    pub(crate) fn spawn(directory: &Path) -> Self {
+        let child = Arc::new(Mutex::new(ChildState::NotStarted));
+        let (sender, receiver) = mpsc::channel();
+        let shared_child = child.clone();
+        let thread_directory = directory.to_path_buf();
+        std::thread::spawn(move || {
+            if let Some(output) = run_status(&thread_directory, &shared_child) {
+                let _ = sender.send(output);
+            }
+        });
+        PendingStatus {
+            directory: directory.to_path_buf(),
+            child,
+            receiver: Mutex::new(receiver),
+            output: OnceLock::new(),
+        }
+    }
+
+    /// Wait for the status until `deadline` and roll it up for `directory`,
+    /// the one it was started for or one under it. `None` if it takes longer
+    /// or fails.
+    pub(crate) fn wait(
+        &self,
+        deadline: Option<Instant>,
+        directory: &Path,
+    ) -> Option<HashMap<OsString, GitStatus>> {
+        let output = match self.output.get() {
+            Some(output) => output,
+            None => {
+                let received = list::recv_until(&*self.receiver.lock().ok()?, deadline);
+                let Ok(output) = received else {
+                    self.kill();
+                    return None;
+                };
+                self.output.get_or_init(|| output)
+            }
+        };
+        let subdirectory = directory.strip_prefix(&self.directory).ok()?;
+        Some(parse_statuses(
+            &output.records,
+            &output.prefix.join(subdirectory),
+        ))
+    }
+
+    /// A status that never arrives, like `git status` on a huge work tree.
+    #[cfg(test)]
+    pub(crate) fn never(directory: &Path) -> (Self, mpsc::Sender<StatusOutput>) {
+        let (sender, receiver) = mpsc::channel();
+        let pending = PendingStatus {
+            directory: directory.to_path_buf(),
+            child: Arc::new(Mutex::new(ChildState::NotStarted)),
+            receiver: Mutex::new(receiver),
+            output: OnceLock::new(),
+        };
+        (pending, sender)
+    }
+
+    fn kill(&self) {
+        let Ok(mut state) = self.child.lock() else {
+            return;
+        };
+        if let ChildState::Running(mut child) = std::mem::replace(&mut *state, ChildState::Finished)
+        {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// `None` if `directory` isn't in a work tree or `git status` fails.
+fn run_status(directory: &Path, shared_child: &Mutex<ChildState>) -> Option<StatusOutput> {
+    let directory = directory.canonicalize().ok()?;
+    let root = directory
+        .ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())?;
+    let prefix = directory.strip_prefix(root).ok()?;
+
+    // Spawn while holding the lock, so a caller that gave up can't miss the child
+    let mut state = shared_child.lock().ok()?;
+    if !matches!(*state, ChildState::NotStarted) {
+        return None;
+    }
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(&directory)
+        .args([
+            "status",
+            "--porcelain=v1",
+            "-z",
+            "--ignored",
+            "--untracked-files=normal",
+            "--no-renames",
+            "--",
+            ".",
+        ])
+        // Don't take the index lock, the user may be running git right now
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut stdout = child.stdout.take()?;
+    *state = ChildState::Running(child);
+    drop(state);
+
+    let mut output = vec![];
+    stdout.read_to_end(&mut output).ok()?;
+    let state = std::mem::replace(&mut *shared_child.lock().ok()?, ChildState::Finished);
+    // Not running if the caller gave up waiting and killed it
+    let ChildState::Running(mut child) = state else {
+        return None;
+    };
+    if !child.wait().ok()?.success() {
+        return None;
+    }
+    Some(StatusOutput {
+        records: output,
+        prefix: prefix.to_path_buf(),
+    })
+}
+
+/// Roll up porcelain v1 records into the status of each top level entry.
+fn parse_statuses(output: &[u8], prefix: &Path) -> HashMap<OsString, GitStatus> {
+    let mut statuses = HashMap::new();
+    for record in output.split(|&byte| byte == 0) {
+        if record.len() < 4 {
+            continue;
+        }
+        let Some(status) = parse_status(record[0], record[1]) else {
+            continue;
+        };
+        let path = Path::new(OsStr::from_bytes(&record[3..]));
+        let Ok(relative) = path.strip_prefix(prefix) else {
+            continue;
+        };
+        let mut components = relative.components();
+        let Some(first) = components.next() else {
+            continue;
+        };
+        // An ignored file doesn't make its directory ignored
+        if status == GitStatus::Ignored && components.next().is_some() {
+            continue;
+        }
+        let entry = statuses
+            .entry(first.as_os_str().to_os_string())
+            .or_insert(status);
+        *entry = (*entry).max(status);
+    }
+    statuses
+}
+
+fn parse_status(index: u8, work_tree: u8) -> Option<GitStatus> {
+    match (index, work_tree) {
+        (b'!', b'!') => Some(GitStatus::Ignored),
+        (b'?', b'?') => Some(GitStatus::Untracked),
+        (b'U', _) | (_, b'U') | (b'A', b'A') | (b'D', b'D') => Some(GitStatus::Conflicted),
+        (_, b'M') | (_, b'D') | (_, b'T') => Some(GitStatus::Modified),
+        (b' ', b' ') => None,
+        _ => Some(GitStatus::Staged),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(records: &[&str], prefix: &str) -> HashMap<OsString, GitStatus> {
+        let output: Vec<u8> = records
+            .iter()
+            .flat_map(|record| record.bytes().chain([0]))
+            .collect();
+        parse_statuses(&output, Path::new(prefix))
+    }
+
+    fn status_of(statuses: &HashMap<OsString, GitStatus>, name: &str) -> Option<GitStatus> {
+        statuses.get(OsStr::new(name)).copied()
+    }
+
+    #[test]
+    fn unmerged_records_are_conflicts() {
+        let statuses = parse(
+            &["AA both_added", "UU both_modified", "DD both_deleted"],
+            "",
+        );
+        for name in ["both_added", "both_modified", "both_deleted"] {
+            assert_eq!(status_of(&statuses, name), Some(GitStatus::Conflicted));
+        }
+    }
+
+    #[test]
+    fn directories_roll_up_the_most_important_status() {
+        let statuses = parse(
+            &[
+                "?? src/new.rs",
+                "A  src/added.rs",
+                " M src/lib.rs",
+                "?? docs/a.md",
+            ],
+            "",
+        );
+        assert_eq!(status_of(&statuses, "src"), Some(GitStatus::Modified));
+        assert_eq!(status_of(&statuses, "docs"), Some(GitStatus::Untracked));
+        let statuses = parse(&[" M src/lib.rs", "UU src/sub/x.rs"], "");
+        assert_eq!(status_of(&statuses, "src"), Some(GitStatus::Conflicted));
+    }
+
+    #[test]
+    fn ignored_file_in_subdirectory_leaves_directory_unmarked() {
+        let statuses = parse(&["!! target/", "!! src/debug.log", "!! .env"], "");
+        assert_eq!(status_of(&statuses, "target"), Some(GitStatus::Ignored));
+        assert_eq!(status_of(&statuses, ".env"), Some(GitStatus::Ignored));
+        assert_eq!(status_of(&statuses, "src"), None);
+    }
+
+    #[test]
+    fn paths_are_relative_to_the_listed_directory() {
+        let statuses = parse(&[" M src/lib.rs", "?? docs/guide.md"], "src");
+        assert_eq!(status_of(&statuses, "lib.rs"), Some(GitStatus::Modified));
+        assert_eq!(statuses.len(), 1);
+    }
+}
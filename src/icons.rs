@@ -0,0 +1,95 @@
+use std::ffi::OsStr;
+
+use crate::FileType;
+
+const DIR: char = '\u{f115}';
+const FILE: char = '\u{f15b}';
+const SYMLINK: char = '\u{f481}';
+const SOCKET: char = '\u{f1e6}';
+const FIFO: char = '\u{f07e5}';
+const DEVICE: char = '\u{f0a0}';
+const UNKNOWN: char = '\u{f128}';
+
+/// Nerd Font glyph for an entry, by well-known name, then extension, then type.
+pub fn get_icon(name: &OsStr, file_type: FileType) -> char {
+    let name = name.to_string_lossy();
+    if let Some(icon) = icon_for_name(&name, file_type) {
+        return icon;
+    }
+    match file_type {
+        FileType::Dir => DIR,
+        FileType::Symlink => SYMLINK,
+        FileType::Socket => SOCKET,
+        FileType::Fifo => FIFO,
+        FileType::BlockDevice | FileType::CharDevice => DEVICE,
+        FileType::Unknown => UNKNOWN,
+        FileType::File => name
+            .rsplit_once('.')
+            .filter(|(stem, _)| !stem.is_empty())
+            .and_then(|(_, extension)| icon_for_extension(&extension.to_ascii_lowercase()))
+            .unwrap_or(FILE),
+    }
+}
+
+fn icon_for_name(name: &str, file_type: FileType) -> Option<char> {
+    let icon = if file_type.is_dir() {
+        match name {
+            ".git" => '\u{e5fb}',
+            ".github" => '\u{e5fd}',
+            "node_modules" => '\u{e5fa}',
+            ".config" => '\u{e5fc}',
+            _ => return None,
+        }
+    } else {
+        match name {
+            "Cargo.toml" | "Cargo.lock" => '\u{e7a8}',
+            "Dockerfile" | "docker-compose.yml" | "compose.yaml" => '\u{f308}',
+            "Makefile" | "makefile" | "GNUmakefile" | "CMakeLists.txt" => '\u{e779}',
+            ".git" | ".gitignore" | ".gitattributes" | ".gitmodules" => '\u{f1d3}',
+            "package.json" | "package-lock.json" => '\u{e718}',
+            "flake.nix" | "flake.lock" => '\u{f313}',
+            "LICENSE" | "LICENSE-MIT" | "LICENSE-APACHE" | "COPYING" => '\u{f02d}',
+            "README" | "README.md" => '\u{f48a}',
+            _ => return None,
+        }
+    };
+    Some(icon)
+}
+
+fn icon_for_extension(extension: &str) -> Option<char> {
+    let icon = match extension {
+        "rs" => '\u{e7a8}',
+        "py" => '\u{e606}',
+        "js" | "mjs" | "cjs" => '\u{e74e}',
+        "ts" | "tsx" => '\u{e628}',
+        "go" => '\u{e626}',
+        "c" => '\u{e61e}',
+        "h" | "hpp" => '\u{f0fd}',
+        "cpp" | "cc" | "cxx" => '\u{e61d}',
+        "java" | "jar" => '\u{e738}',
+        "rb" => '\u{e21e}',
+        "lua" => '\u{e620}',
+        "nix" => '\u{f313}',
+        "vim" => '\u{e62b}',
+        "sh" | "bash" | "zsh" | "fish" => '\u{f489}',
+        "md" | "markdown" => '\u{f48a}',
+        "json" => '\u{e60b}',
+        "toml" => '\u{e6b2}',
+        "yml" | "yaml" => '\u{e6a8}',
+        "html" | "htm" => '\u{f13b}',
+        "css" | "scss" => '\u{e749}',
+        "sql" | "db" | "sqlite" => '\u{f1c0}',
+        "csv" | "tsv" => '\u{f1c3}',
+        "txt" => '\u{f15c}',
+        "log" => '\u{f18d}',
+        "lock" => '\u{f023}',
+        "diff" | "patch" => '\u{f440}',
+        "pdf" => '\u{f1c1}',
+        "zip" | "tar" | "gz" | "tgz" | "xz" | "zst" | "bz2" | "7z" | "rar" => '\u{f410}',
+        "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "ico" | "bmp" => '\u{f1c5}',
+        "mp3" | "flac" | "wav" | "ogg" | "m4a" => '\u{f001}',
+        "mp4" | "mkv" | "webm" | "mov" | "avi" => '\u{f03d}',
+        _ => return None,
+    };
+    Some(icon)
+}
@@ -0,0 +1,116 @@
+use clap::ValueEnum;
+use clap_complete::generate;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+    Elvish,
+}
+
+/// Hook that previews the new directory whenever the shell changes directory.
+pub fn hook(shell: Shell, args: &[String]) -> String {
+    let command = std::iter::once("ls-preview")
+        .chain(args.iter().map(String::as_str))
+        .map(|arg| quote(shell, arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let template = match shell {
+        Shell::Bash => BASH,
+        Shell::Zsh => ZSH,
+        Shell::Fish => FISH,
+        Shell::Nushell => NUSHELL,
+        Shell::Elvish => ELVISH,
+    };
+    template.replace("{command}", &command)
+}
+
+/// Completion script for the command line of `command`.
+pub fn completions(shell: Shell, command: &mut clap::Command) -> String {
+    let mut script = vec![];
+    let name = command.get_name().to_string();
+    match shell {
+        Shell::Bash => generate(clap_complete::Shell::Bash, command, name, &mut script),
+        Shell::Zsh => generate(clap_complete::Shell::Zsh, command, name, &mut script),
+        Shell::Fish => generate(clap_complete::Shell::Fish, command, name, &mut script),
+        Shell::Nushell => generate(clap_complete_nushell::Nushell, command, name, &mut script),
+        Shell::Elvish => generate(clap_complete::Shell::Elvish, command, name, &mut script),
+    }
+    String::from_utf8_lossy(&script).into_owned()
+}
+
+fn quote(shell: Shell, arg: &str) -> String {
+    let is_plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=./,:@%+".contains(c));
+    if is_plain {
+        return arg.to_string();
+    }
+    match shell {
+        Shell::Bash | Shell::Zsh => format!("'{}'", arg.replace('\'', r"'\''")),
+        Shell::Fish => format!("'{}'", arg.replace('\\', r"\\").replace('\'', r"\'")),
+        Shell::Elvish => format!("'{}'", arg.replace('\'', "''")),
+        Shell::Nushell => format!("r#'{arg}'#"),
+    }
+}
+
+const BASH: &str = r#"__ls_preview_hook() {
+    local status=$?
+    if [[ "$PWD" != "${__ls_preview_last_pwd-}" ]]; then
+        __ls_preview_last_pwd="$PWD"
+        command {command}
+    fi
+    return $status
+}
+__ls_preview_last_pwd="$PWD"
+if [[ ";${PROMPT_COMMAND[*]:-};" != *";__ls_preview_hook;"* ]]; then
+    PROMPT_COMMAND="__ls_preview_hook${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+"#;
+
+const ZSH: &str = r#"__ls_preview_hook() {
+    if [[ "$PWD" != "${__ls_preview_last_pwd-}" ]]; then
+        __ls_preview_last_pwd="$PWD"
+        command {command}
+    fi
+}
+typeset -g __ls_preview_last_pwd="$PWD"
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd __ls_preview_hook
+"#;
+
+const FISH: &str = r#"set -g __ls_preview_last_pwd $PWD
+function __ls_preview_hook --on-variable PWD
+    status is-command-substitution; and return
+    if test "$PWD" != "$__ls_preview_last_pwd"
+        set -g __ls_preview_last_pwd $PWD
+        command {command}
+    end
+end
+"#;
+
+const NUSHELL: &str = r#"export-env {
+    $env.config = (
+        $env.config?
+        | default {}
+        | upsert hooks { default {} }
+        | upsert hooks.env_change { default {} }
+        | upsert hooks.env_change.PWD { default [] }
+    )
+    $env.config.hooks.env_change.PWD = ($env.config.hooks.env_change.PWD | append {|before, after|
+        if $before != $after { ^{command} }
+    })
+}
+"#;
+
+const ELVISH: &str = r#"var __ls_preview_last_pwd = $pwd
+set after-chdir = [$@after-chdir {|_|
+    if (not-eq $pwd $__ls_preview_last_pwd) {
+        set __ls_preview_last_pwd = $pwd
+        e:{command}
+    }
+}]
+"#;
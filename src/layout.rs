@@ -0,0 +1,222 @@
+use crate::{get_entry_color_and_indicator, Entry};
+
+/// Space between columns.
+const GAP: u16 = 2;
+
+/// Order in which entries fill the columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Layout {
+    /// Left to right, then top to bottom, like `ls -x`
+    #[default]
+    Across,
+    /// Top to bottom, then left to right, like `ls -C`
+    Down,
+}
+
+/// Entries arranged in columns, each as wide as its widest entry.
+#[derive(Debug, Clone)]
+pub(crate) struct Grid {
+    /// Number of entries that fit, the rest is summarized in the footer.
+    pub(crate) shown: usize,
+    /// The slot after the shown entries holds the footer.
+    pub(crate) footer: bool,
+    pub(crate) rows: usize,
+    /// Width of each column, including the gap after it.
+    pub(crate) widths: Vec<u16>,
+    columns: usize,
+    layout: Layout,
+}
+
+impl Grid {
+    /// The grid with the most columns that fits in `width`, which is the one
+    /// showing the most entries in `max_lines` rows.
+    ///
+    /// With `footer`, a footer is needed even if all entries fit.
+    pub(crate) fn new(
+        entries: &[Entry],
+        width: Option<u16>,
+        max_lines: usize,
+        layout: Layout,
+        footer: bool,
+    ) -> Self {
+        // Narrowest column is a single cell and the gap
+        let max_columns = width.map(|width| (width / (1 + GAP)) as usize).unwrap_or(1);
+        (1..=max_columns.max(1))
+            .rev()
+            .map(|columns| Grid::with_columns(entries, columns, max_lines, layout, footer))
+            .find(|grid| {
+                let line_width = grid
+                    .widths
+                    .iter()
+                    .map(|&width| width as usize)
+                    .sum::<usize>();
+                grid.widths.len() <= 1
+                    || width.is_none_or(|width| {
+                        line_width.saturating_sub(GAP as usize) <= width as usize
+                    })
+            })
+            .expect("a single column always fits")
+    }
+
+    fn with_columns(
+        entries: &[Entry],
+        columns: usize,
+        max_lines: usize,
+        layout: Layout,
+        footer: bool,
+    ) -> Self {
+        let capacity = columns * max_lines;
+        let (shown, footer) = if entries.len() + usize::from(footer) <= capacity {
+            (entries.len(), footer)
+        } else {
+            (capacity - 1, true)
+        };
+        let slots = shown + usize::from(footer);
+        let rows = slots.div_ceil(columns);
+        let used_columns = match layout {
+            Layout::Across => slots.min(columns),
+            Layout::Down if rows == 0 => 0,
+            Layout::Down => slots.div_ceil(rows),
+        };
+        let mut grid = Grid {
+            shown,
+            footer,
+            rows,
+            widths: vec![0; used_columns],
+            columns,
+            layout,
+        };
+        let widths = entries[..shown]
+            .iter()
+            .map(cell_width)
+            // The footer is shortened to fit what's left of its row
+            .chain(footer.then_some(1));
+        for (slot, width) in widths.enumerate() {
+            let column = grid.column(slot);
+            grid.widths[column] = grid.widths[column].max(width + GAP);
+        }
+        grid
+    }
+
+    /// Number of occupied slots, including the footer.
+    pub(crate) fn slots(&self) -> usize {
+        self.shown + usize::from(self.footer)
+    }
+
+    /// Slot at the given row and column, `None` if it's past the end.
+    pub(crate) fn slot(&self, row: usize, column: usize) -> Option<usize> {
+        let slot = match self.layout {
+            Layout::Across => row * self.columns + column,
+            Layout::Down => column * self.rows + row,
+        };
+        (column < self.widths.len() && slot < self.slots()).then_some(slot)
+    }
+
+    fn column(&self, slot: usize) -> usize {
+        match self.layout {
+            Layout::Across => slot % self.columns,
+            Layout::Down => slot / self.rows,
+        }
+    }
+}
+
+/// Width of an entry with its git marker, icon and indicator.
+pub(crate) fn cell_width(entry: &Entry) -> u16 {
+    let indicator = get_entry_color_and_indicator(entry).1;
+    entry.display_width() + indicator.len() as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileType;
+
+    fn entries(names: &[&str]) -> Vec<Entry> {
+        names
+            .iter()
+            .map(|name| Entry {
+                name: name.into(),
+                file_type: FileType::File,
+                width: name.len() as u16,
+                shortened: None,
+                git_status: None,
+                is_new: false,
+                icon: None,
+                metadata: None,
+                link_target: None,
+                link_path: None,
+            })
+            .collect()
+    }
+
+    /// The names in each row, `…` for the footer.
+    fn rows(entries: &[Entry], width: u16, max_lines: usize, layout: Layout) -> Vec<Vec<String>> {
+        let grid = Grid::new(entries, Some(width), max_lines, layout, false);
+        (0..grid.rows)
+            .map(|row| {
+                (0..grid.widths.len())
+                    .map_while(|column| grid.slot(row, column))
+                    .map(|slot| match entries.get(slot) {
+                        Some(entry) if slot < grid.shown => entry.display_name().into_owned(),
+                        _ => "…".to_string(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn across_fills_rows_first() {
+        let entries = entries(&["a", "b", "c", "d", "e"]);
+        assert_eq!(
+            rows(&entries, 9, 2, Layout::Across),
+            [vec!["a", "b", "c"], vec!["d", "e"]]
+        );
+    }
+
+    #[test]
+    fn down_fills_columns_first_and_leaves_the_last_one_short() {
+        let entries = entries(&["a", "b", "c", "d", "e"]);
+        assert_eq!(
+            rows(&entries, 9, 2, Layout::Down),
+            [vec!["a", "c", "e"], vec!["b", "d"]]
+        );
+        let grid = Grid::new(&entries, Some(9), 3, Layout::Down, false);
+        assert_eq!((grid.rows, grid.widths.len()), (2, 3));
+    }
+
+    #[test]
+    fn columns_are_as_wide_as_their_widest_entry() {
+        let entries = entries(&["aaaa", "b", "c"]);
+        let grid = Grid::new(&entries, Some(8), 2, Layout::Across, false);
+        assert_eq!(grid.widths, [6, 3]);
+        assert_eq!(grid.shown, 3);
+        // Three columns would need 10
+        let grid = Grid::new(&entries, Some(10), 2, Layout::Across, false);
+        assert_eq!(grid.widths, [6, 3, 3]);
+    }
+
+    #[test]
+    fn footer_takes_the_slot_after_the_shown_entries() {
+        let entries = entries(&["a", "b", "c", "d", "e", "f", "g"]);
+        assert_eq!(
+            rows(&entries, 9, 2, Layout::Across),
+            [vec!["a", "b", "c"], vec!["d", "e", "…"]]
+        );
+        assert_eq!(
+            rows(&entries, 9, 2, Layout::Down),
+            [vec!["a", "c", "e"], vec!["b", "d", "…"]]
+        );
+        let grid = Grid::new(&entries, Some(9), 2, Layout::Across, false);
+        assert_eq!((grid.shown, grid.footer), (5, true));
+    }
+
+    #[test]
+    fn footer_can_be_needed_when_everything_fits() {
+        let entries = entries(&["a", "b", "c"]);
+        let grid = Grid::new(&entries, Some(9), 2, Layout::Across, true);
+        assert_eq!((grid.shown, grid.footer, grid.rows), (3, true, 2));
+        assert_eq!(grid.slot(1, 0), Some(3));
+        assert_eq!(grid.slot(1, 1), None);
+    }
+}
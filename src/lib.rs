@@ -0,0 +1,645 @@
+//! Show a preview of the directory contents.
+//!
+//! ```no_run
+//! let listing = ls_preview::Preview::new(".").max_lines(2).run()?;
+//! ls_preview::render(&mut std::io::stdout(), &listing)?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use std::borrow::Cow;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use layout::Grid;
+
+mod archive;
+mod cache;
+mod file_type;
+mod filter;
+mod format;
+mod git;
+mod icons;
+mod layout;
+mod list;
+mod render;
+mod rev;
+mod seen;
+mod sort;
+mod source;
+mod store;
+mod style;
+mod tree;
+mod truncate;
+
+pub use archive::ArchiveSource;
+pub use cache::refresh_cache;
+pub use file_type::FileType;
+pub use filter::NameFilter;
+pub use format::Format;
+pub use git::GitStatus;
+pub use icons::get_icon;
+pub use layout::Layout;
+pub use render::{render, Renderer};
+pub use rev::GitRevSource;
+pub use sort::{Collation, SortBy};
+pub use source::{DirSource, Entries, FsSource, MemorySource};
+pub use store::{default_cache_dir, default_config_dir, default_state_dir};
+pub use style::{get_color_and_indicator, get_entry_color_and_indicator, get_git_marker, LsColors};
+pub use tree::render_tree;
+
+pub const MIN_TAB_WIDTH: u16 = 8;
+pub const MAX_NAME_WIDTH: u16 = 40;
+/// Width assumed when it can't be told, as `ls` does.
+pub const DEFAULT_WIDTH: u16 = 80;
+pub const TIME_LIMIT: Duration = Duration::from_millis(10);
+
+/// Builder for a directory preview.
+#[derive(Debug, Clone)]
+pub struct Preview {
+    directory: PathBuf,
+    source: Option<Arc<dyn DirSource>>,
+    max_lines: usize,
+    width: Option<u16>,
+    layout: Layout,
+    max_name_width: u16,
+    min_tab_width: u16,
+    time_limit: Option<Duration>,
+    git_status: bool,
+    sort_by: SortBy,
+    collation: Collation,
+    reverse: bool,
+    dirs_first: bool,
+    all: bool,
+    respect_ignore: bool,
+    name_filter: NameFilter,
+    metadata: bool,
+    follow_links: bool,
+    link_targets: bool,
+    icons: bool,
+    cache_dir: Option<PathBuf>,
+    state_dir: Option<PathBuf>,
+    /// A `git status` started for an enclosing directory, see [`render_tree`].
+    shared_status: Option<Arc<git::PendingStatus>>,
+}
+
+/// A single listed entry.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub name: OsString,
+    pub file_type: FileType,
+    /// Display width of the name as shown, without the indicator.
+    pub width: u16,
+    /// The name with its middle replaced by `…` when it's too wide to show.
+    pub shortened: Option<String>,
+    pub git_status: Option<GitStatus>,
+    /// Not in the directory when it was last previewed, see [`Preview::state_dir`].
+    pub is_new: bool,
+    /// Nerd Font glyph shown before the name, see [`Preview::icons`].
+    pub icon: Option<char>,
+    /// Only fetched when needed, e.g. to sort by time or size.
+    pub metadata: Option<fs::Metadata>,
+    /// For symlinks, what they point to.
+    pub link_target: Option<LinkTarget>,
+    /// For symlinks, the path they contain, see [`Preview::link_targets`].
+    pub link_path: Option<PathBuf>,
+}
+
+/// What a symlink points to.
+#[derive(Debug, Clone)]
+pub enum LinkTarget {
+    Found(fs::Metadata),
+    /// The target doesn't exist or can't be reached.
+    Broken,
+    /// Resolving the target leads back to a symlink already followed.
+    Loop,
+}
+
+impl Entry {
+    /// The name as shown.
+    pub fn display_name(&self) -> Cow<'_, str> {
+        match &self.shortened {
+            Some(shortened) => Cow::Borrowed(shortened),
+            None => self.name.to_string_lossy(),
+        }
+    }
+
+    /// Display width including the git marker, new marker, icon and link
+    /// target, without the indicator.
+    pub fn display_width(&self) -> u16 {
+        // Each is followed by a space, Nerd Font glyphs take a single cell
+        self.width
+            + if self.git_status.is_some() { 2 } else { 0 }
+            + if style::new_marker(self).is_some() {
+                2
+            } else {
+                0
+            }
+            + if self.icon.is_some() { 2 } else { 0 }
+            + self.link_path.as_deref().map_or(0, link_path_width)
+    }
+}
+
+/// The outcome of a preview: the entries to show and how they were chosen.
+#[derive(Debug, Clone)]
+pub struct Listing {
+    pub directory: PathBuf,
+    /// Entries to show, in display order.
+    pub entries: Vec<Entry>,
+    /// Files were dropped because there were too many entries to fit.
+    pub dirs_only: bool,
+    /// Entries dropped by the directories-only fallback, in display order.
+    pub dropped: Vec<Entry>,
+    /// Number of dotfiles, ignored and filtered out entries skipped while listing.
+    pub hidden: usize,
+    /// Listing stopped early because it ran out of time.
+    pub timed_out: bool,
+    /// Every entry of the directory was listed.
+    pub complete: bool,
+    /// Entries came from the cache.
+    pub cached: bool,
+    /// Number of entries gone since the directory was last previewed, see
+    /// [`Preview::state_dir`].
+    pub removed: usize,
+    pub width: Option<u16>,
+    pub layout: Layout,
+    pub max_lines: usize,
+}
+
+impl Listing {
+    /// Number of lines the listing takes when rendered.
+    pub fn lines(&self) -> usize {
+        self.grid().rows
+    }
+
+    /// Number of entries shown in columns, the rest is summarized in the footer.
+    pub fn shown(&self) -> usize {
+        self.grid().shown
+    }
+
+    /// Number of listed entries that aren't shown.
+    pub fn omitted(&self) -> usize {
+        self.entries.len() - self.shown() + self.dropped.len()
+    }
+
+    /// Whether the last slot says what was left out, or that listing was cut short.
+    pub fn has_footer(&self) -> bool {
+        self.grid().footer
+    }
+
+    pub(crate) fn grid(&self) -> Grid {
+        let footer = !self.dropped.is_empty() || !self.complete || self.removed > 0;
+        Grid::new(
+            &self.entries,
+            self.width,
+            self.max_lines,
+            self.layout,
+            footer,
+        )
+    }
+}
+
+/// Width of ` → target`.
+fn link_path_width(link_path: &Path) -> u16 {
+    3 + console::measure_text_width(&link_path.to_string_lossy()) as u16
+}
+
+impl Preview {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Preview {
+            directory: directory.into(),
+            source: None,
+            max_lines: 2,
+            width: None,
+            layout: Layout::default(),
+            max_name_width: MAX_NAME_WIDTH,
+            min_tab_width: MIN_TAB_WIDTH,
+            time_limit: Some(TIME_LIMIT),
+            git_status: false,
+            sort_by: SortBy::default(),
+            collation: Collation::default(),
+            reverse: false,
+            dirs_first: false,
+            all: false,
+            respect_ignore: false,
+            name_filter: NameFilter::default(),
+            metadata: false,
+            follow_links: false,
+            link_targets: false,
+            icons: false,
+            cache_dir: None,
+            state_dir: None,
+            shared_status: None,
+        }
+    }
+
+    /// List entries from `source` instead of the directory, which is then
+    /// only used to name the listing.
+    pub fn source(mut self, source: Arc<dyn DirSource>) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Maximum number of lines to display.
+    pub fn max_lines(mut self, max_lines: usize) -> Self {
+        self.max_lines = max_lines;
+        self
+    }
+
+    /// Terminal width in columns, `None` if unknown, then entries are shown
+    /// one per line.
+    pub fn width(mut self, width: Option<u16>) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Whether entries fill the columns across or down.
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Widest name shown in full, longer ones lose their middle but keep
+    /// their extension. Names are also shortened to fit the width. 0 for no
+    /// limit besides the width.
+    pub fn max_name_width(mut self, max_name_width: u16) -> Self {
+        self.max_name_width = max_name_width;
+        self
+    }
+
+    /// Narrowest column assumed when estimating how many entries can fit.
+    pub fn min_tab_width(mut self, min_tab_width: u16) -> Self {
+        self.min_tab_width = min_tab_width.max(1);
+        self
+    }
+
+    /// Time budget for the whole preview, including `git status`.
+    ///
+    /// When it runs out, the entries listed so far are used. The listing
+    /// continues on a background thread until its next entry, so a directory
+    /// on a hanging mount can't block the caller.
+    ///
+    /// `None` waits for every entry, so sources that can't hang, such as a
+    /// [`MemorySource`], always give the same listing.
+    pub fn time_limit(mut self, time_limit: Option<Duration>) -> Self {
+        self.time_limit = time_limit;
+        self
+    }
+
+    /// Mark entries with their git status when inside a work tree.
+    ///
+    /// `git status` runs alongside the listing and is given up on if it
+    /// doesn't finish within the time limit.
+    pub fn git_status(mut self, git_status: bool) -> Self {
+        self.git_status = git_status;
+        self
+    }
+
+    pub fn sort_by(mut self, sort_by: SortBy) -> Self {
+        self.sort_by = sort_by;
+        self
+    }
+
+    /// How names are compared when sorting by name or breaking ties.
+    ///
+    /// [`Collation::Locale`] uses the process's `LC_COLLATE`, which the caller
+    /// sets with `setlocale(LC_COLLATE, "")` before starting any thread, since
+    /// the locale is global. Until then it's the C locale, which is byte-wise.
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.collation = collation;
+        self
+    }
+
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    /// Show directories before other entries.
+    pub fn dirs_first(mut self, dirs_first: bool) -> Self {
+        self.dirs_first = dirs_first;
+        self
+    }
+
+    /// Include entries whose names start with a dot.
+    pub fn all(mut self, all: bool) -> Self {
+        self.all = all;
+        self
+    }
+
+    /// Hide entries matched by `.gitignore`, `.ignore` and the global git excludes.
+    ///
+    /// Only applies to the filesystem, not to a custom [`Preview::source`].
+    pub fn respect_ignore(mut self, respect_ignore: bool) -> Self {
+        self.respect_ignore = respect_ignore;
+        self
+    }
+
+    /// Show only entries whose names pass `name_filter`. The rest are left out
+    /// while listing, so they don't take up room or make the preview fall
+    /// back to directories only.
+    pub fn name_filter(mut self, name_filter: NameFilter) -> Self {
+        self.name_filter = name_filter;
+        self
+    }
+
+    /// Fetch the metadata of the shown entries, e.g. for
+    /// [`LsColors::needs_metadata`], within the time limit. Sorting by time or
+    /// size fetches it for every entry regardless.
+    pub fn metadata(mut self, metadata: bool) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Classify symlinks by what they point to, so a link to a directory is
+    /// shown and counted as one. Broken links and loops stay symlinks.
+    pub fn follow_links(mut self, follow_links: bool) -> Self {
+        self.follow_links = follow_links;
+        self
+    }
+
+    /// Show where symlinks point after their name, when that doesn't cost
+    /// any entries their place.
+    pub fn link_targets(mut self, link_targets: bool) -> Self {
+        self.link_targets = link_targets;
+        self
+    }
+
+    /// Prefix entries with a Nerd Font glyph for their name, extension or type.
+    pub fn icons(mut self, icons: bool) -> Self {
+        self.icons = icons;
+        self
+    }
+
+    /// Serve the listing from a cache in `cache_dir` while the directory's
+    /// mtime is unchanged, and store complete listings there.
+    ///
+    /// Listings that aren't complete aren't cached, see [`refresh_cache`].
+    pub fn cache_dir(mut self, cache_dir: Option<PathBuf>) -> Self {
+        self.cache_dir = cache_dir;
+        self
+    }
+
+    /// Compare with the entries stored in `state_dir` the last time the
+    /// directory was listed completely, marking new entries and counting
+    /// removed ones, and store the current ones there.
+    ///
+    /// Entries are only marked on the second and later previews.
+    pub fn state_dir(mut self, state_dir: Option<PathBuf>) -> Self {
+        self.state_dir = state_dir;
+        self
+    }
+
+    pub fn run(&self) -> io::Result<Listing> {
+        let deadline = self.deadline();
+        self.run_until(deadline, deadline)
+    }
+
+    /// When the time limit runs out if it starts now, `None` without one.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.time_limit
+            .map(|time_limit| Instant::now() + time_limit)
+    }
+
+    /// Run with a deadline shared with other previews instead of the time
+    /// limit, waiting for `git status` only until `status_deadline`.
+    pub(crate) fn run_until(
+        &self,
+        deadline: Option<Instant>,
+        status_deadline: Option<Instant>,
+    ) -> io::Result<Listing> {
+        if self.max_lines == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "`max_lines` must be greater than 0",
+            ));
+        }
+
+        let max_columns = self
+            .width
+            .map(|width| (width / self.min_tab_width) as usize)
+            .unwrap_or(0)
+            .max(1);
+
+        let max_items = max_columns * self.max_lines;
+
+        let pending_status = self.pending_status();
+        let listing = list::spawn(self.clone());
+
+        let mut entries = vec![];
+        let mut num_dirs = 0;
+        let mut hidden = 0;
+        let mut too_many_dirs = false;
+        let mut ran_out_of_time_listing = false;
+        let mut complete = false;
+        let mut cached = false;
+        let mut removed = 0;
+
+        loop {
+            match list::recv_until(&listing, deadline) {
+                Ok(list::Message::Entry(entry)) => {
+                    if entry.file_type.is_dir() {
+                        num_dirs += 1;
+                    }
+                    entries.push(entry);
+                    // A cached listing is all there at once, so it's shown in order
+                    if num_dirs >= max_items && !cached {
+                        too_many_dirs = true;
+                        break;
+                    }
+                }
+                Ok(list::Message::Hidden) => hidden += 1,
+                Ok(list::Message::Cached) => cached = true,
+                Ok(list::Message::Removed(count)) => removed = count,
+                Ok(list::Message::Error(err)) => return Err(err),
+                Ok(list::Message::Done) => {
+                    complete = true;
+                    break;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {
+                    ran_out_of_time_listing = true;
+                    break;
+                }
+            }
+        }
+
+        let statuses =
+            pending_status.and_then(|pending| pending.wait(status_deadline, &self.directory));
+        if let Some(statuses) = statuses {
+            for entry in &mut entries {
+                entry.git_status = statuses.get(&entry.name).copied();
+            }
+        }
+
+        if self.link_targets && !self.link_targets_fit(&mut entries) {
+            for entry in &mut entries {
+                entry.link_path = None;
+            }
+        }
+        for entry in &mut entries {
+            self.shorten_name(entry);
+        }
+
+        // Only directories if all entries don't fit in the actual columns, and
+        // otherwise as many files as fit
+        let dirs_only = entries.iter().any(|entry| entry.file_type.is_dir())
+            && (ran_out_of_time_listing
+                || too_many_dirs
+                || Grid::new(&entries, self.width, self.max_lines, self.layout, false).shown
+                    < entries.len());
+        let (mut entries, mut dropped): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .partition(|entry| !dirs_only || entry.file_type.is_dir());
+
+        for entries in [&mut entries, &mut dropped] {
+            sort::sort_entries(
+                entries,
+                self.sort_by,
+                self.collation,
+                self.reverse,
+                self.dirs_first,
+            );
+        }
+
+        let mut listing = Listing {
+            directory: self.directory.clone(),
+            entries,
+            dirs_only,
+            dropped,
+            hidden,
+            timed_out: ran_out_of_time_listing,
+            complete,
+            cached,
+            removed,
+            width: self.width,
+            layout: self.layout,
+            max_lines: self.max_lines,
+        };
+        if self.metadata {
+            self.fetch_shown_metadata(&mut listing, deadline);
+        }
+        Ok(listing)
+    }
+
+    /// Fetch the metadata of the shown entries that don't have it yet, so a
+    /// huge directory doesn't take a stat per entry.
+    ///
+    /// It can make an entry's indicator wider, so names are shortened again.
+    fn fetch_shown_metadata(&self, listing: &mut Listing, deadline: Option<Instant>) {
+        let shown = listing.shown();
+        let mut pending = vec![];
+        for (i, entry) in listing.entries[..shown].iter_mut().enumerate() {
+            match &entry.link_target {
+                _ if entry.metadata.is_some() => {}
+                Some(LinkTarget::Found(target)) if self.follow_links => {
+                    entry.metadata = Some(target.clone());
+                }
+                _ => pending.push((i, entry.name.clone())),
+            }
+        }
+        if !pending.is_empty() {
+            let fetched = list::spawn_metadata(self.dir_source(), pending);
+            while let Ok((i, metadata)) = list::recv_until(&fetched, deadline) {
+                listing.entries[i].metadata = Some(metadata);
+            }
+        }
+        for entry in &mut listing.entries[..shown] {
+            self.shorten_name(entry);
+        }
+    }
+
+    /// The `git status` to mark entries with, shared or started for this preview.
+    pub(crate) fn pending_status(&self) -> Option<Arc<git::PendingStatus>> {
+        if !self.git_status {
+            return None;
+        }
+        if let Some(pending) = &self.shared_status {
+            return Some(pending.clone());
+        }
+        if self.source.is_some() {
+            return None;
+        }
+        Some(Arc::new(git::PendingStatus::spawn(&self.directory)))
+    }
+
+    /// Where entries are listed from.
+    pub(crate) fn dir_source(&self) -> Arc<dyn DirSource> {
+        self.source
+            .clone()
+            .unwrap_or_else(|| Arc::new(FsSource::new(&self.directory)))
+    }
+
+    /// Whether as many entries fit with link targets as without them.
+    fn link_targets_fit(&self, entries: &mut [Entry]) -> bool {
+        let shown = |entries: &[Entry]| {
+            Grid::new(entries, self.width, self.max_lines, self.layout, false).shown
+        };
+        let with_targets = shown(entries);
+        let link_paths: Vec<_> = entries
+            .iter_mut()
+            .map(|entry| entry.link_path.take())
+            .collect();
+        let without_targets = shown(entries);
+        for (entry, link_path) in entries.iter_mut().zip(link_paths) {
+            entry.link_path = link_path;
+        }
+        with_targets >= without_targets
+    }
+
+    /// Shorten the name of `entry` to the name width limit, and so that with
+    /// its markers and indicator it fits on a line.
+    fn shorten_name(&self, entry: &mut Entry) {
+        let indicator = get_entry_color_and_indicator(entry).1;
+        let decorations = entry.display_width() - entry.width + indicator.len() as u16;
+        let max_width = [
+            (self.max_name_width > 0).then_some(self.max_name_width),
+            self.width.map(|width| width.saturating_sub(decorations)),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        let shortened = max_width.and_then(|max_width| {
+            truncate::truncate_middle(&entry.name.to_string_lossy(), max_width)
+        });
+        if let Some(shortened) = shortened {
+            entry.width = console::measure_text_width(&shortened) as u16;
+            entry.shortened = Some(shortened);
+        }
+    }
+}
+
+/// Width to lay the preview out in, `None` if it can't be told.
+///
+/// In order: the fzf preview window, the terminal on stdout, stderr or
+/// `/dev/tty`, then `COLUMNS`.
+pub fn terminal_width() -> Option<u16> {
+    let env_width = |name| {
+        std::env::var(name)
+            .ok()
+            .and_then(|value| value.trim().parse::<u16>().ok())
+            .filter(|&width| width > 0)
+    };
+    let tty = std::fs::File::open("/dev/tty").ok();
+    env_width("FZF_PREVIEW_COLUMNS")
+        .or_else(|| fd_width(io::stdout().as_raw_fd()))
+        .or_else(|| fd_width(io::stderr().as_raw_fd()))
+        .or_else(|| tty.and_then(|tty| fd_width(tty.as_raw_fd())))
+        .or_else(|| env_width("COLUMNS"))
+}
+
+/// Width of the terminal open on `fd`, `None` if it isn't one.
+fn fd_width(fd: std::os::fd::RawFd) -> Option<u16> {
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut ws) };
+    if result == 0 && ws.ws_col > 0 {
+        Some(ws.ws_col)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::cache::Cache;
+use crate::filter::IgnoreRules;
+use crate::seen::Seen;
+use crate::{get_icon, DirSource, Entry, FileType, LinkTarget, Preview};
+
+#[allow(clippy::large_enum_variant)] // Almost every message is an entry
+pub(crate) enum Message {
+    /// The entries that follow come from the cache.
+    Cached,
+    Entry(Entry),
+    /// An entry was skipped as a dotfile, ignored or filtered out.
+    Hidden,
+    /// Number of entries gone since the directory was last listed completely,
+    /// sent before `Done`.
+    Removed(usize),
+    Error(io::Error),
+    /// Every entry was sent.
+    Done,
+}
+
+/// List the directory on its own thread, so a hanging `read_dir` or `stat`
+/// on a stale network or FUSE mount can't block the caller past its deadline.
+///
+/// The thread stops once the receiver is dropped and it gets to send again,
+/// unless it's filling the cache, then it finishes the listing first.
+pub(crate) fn spawn(preview: Preview) -> mpsc::Receiver<Message> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let message = match list(&preview, &sender) {
+            Ok(()) => Message::Done,
+            Err(err) => Message::Error(err),
+        };
+        let _ = sender.send(message);
+    });
+    receiver
+}
+
+/// Receive the next message, waiting until `deadline` or, without one, for as
+/// long as it takes.
+pub(crate) fn recv_until<T>(
+    receiver: &mpsc::Receiver<T>,
+    deadline: Option<Instant>,
+) -> Result<T, RecvTimeoutError> {
+    match deadline {
+        Some(deadline) => receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+        None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    }
+}
+
+/// Fetch the metadata of the entries at the given indices on their own
+/// thread, for the same reason as `spawn`. Entries without any are skipped.
+pub(crate) fn spawn_metadata(
+    source: Arc<dyn DirSource>,
+    entries: Vec<(usize, OsString)>,
+) -> mpsc::Receiver<(usize, fs::Metadata)> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for (i, name) in entries {
+            if let Ok(Some(metadata)) = source.metadata(&name) {
+                if sender.send((i, metadata)).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    receiver
+}
+
+fn list(preview: &Preview, sender: &mpsc::Sender<Message>) -> io::Result<()> {
+    // Ignore files describe what's on disk, not an archive or a revision
+    let ignore_rules = if preview.respect_ignore && preview.source.is_none() {
+        Some(IgnoreRules::new(&preview.directory))
+    } else {
+        None
+    };
+    // Otherwise only the shown entries need it, see `spawn_metadata`
+    let needs_metadata = preview.sort_by.needs_metadata();
+    let source = preview.dir_source();
+    let seen = preview
+        .state_dir
+        .as_deref()
+        .filter(|_| preview.source.is_none())
+        .and_then(|state_dir| Seen::open(state_dir, &preview.directory));
+    let previous = seen.as_ref().and_then(Seen::read);
+    let is_hidden = |name: &OsStr, is_dir: bool| {
+        (!preview.all && name.to_string_lossy().starts_with("."))
+            || !preview.name_filter.is_match(name)
+            || ignore_rules
+                .as_ref()
+                .is_some_and(|rules| rules.is_ignored(name, is_dir))
+    };
+    // Whether the receiver is still there
+    let send_entry = |name: OsString, file_type: FileType| -> io::Result<bool> {
+        if is_hidden(&name, file_type.is_dir()) {
+            return Ok(sender.send(Message::Hidden).is_ok());
+        }
+        // Symlinks are rare, so their target is always checked to style broken ones
+        let link_target = if file_type.is_symlink() {
+            source.link_target(&name)
+        } else {
+            None
+        };
+        let link_path = if preview.link_targets && file_type.is_symlink() {
+            source.read_link(&name)
+        } else {
+            None
+        };
+        let (file_type, metadata) = match &link_target {
+            Some(LinkTarget::Found(target)) if preview.follow_links => (
+                FileType::from(target.file_type()),
+                needs_metadata.then(|| target.clone()),
+            ),
+            // Like `ls`, an entry that can't be stat'ed is still shown
+            _ if needs_metadata => (file_type, source.metadata(&name).ok().flatten()),
+            _ => (file_type, None),
+        };
+        let width = console::measure_text_width(&name.to_string_lossy()) as u16;
+        let icon = preview.icons.then(|| get_icon(&name, file_type));
+        let is_new = previous
+            .as_ref()
+            .is_some_and(|previous| !previous.contains(&name));
+        let git_status = preview
+            .git_status
+            .then(|| source.git_status(&name))
+            .flatten();
+        let entry = Entry {
+            name,
+            file_type,
+            width,
+            shortened: None,
+            git_status,
+            is_new,
+            icon,
+            metadata,
+            link_target,
+            link_path,
+        };
+        Ok(sender.send(Message::Entry(entry)).is_ok())
+    };
+
+    let cache = preview
+        .cache_dir
+        .as_deref()
+        .filter(|_| preview.source.is_none())
+        .and_then(|cache_dir| Cache::open(cache_dir, &preview.directory));
+    if let Some(cached) = cache.as_ref().and_then(Cache::read) {
+        let _ = sender.send(Message::Cached);
+        for (name, file_type) in cached.iter().cloned() {
+            if !send_entry(name, file_type)? {
+                return Ok(());
+            }
+        }
+        send_removed(
+            sender,
+            seen.as_ref(),
+            previous.as_ref(),
+            &cached,
+            &is_hidden,
+        );
+        return Ok(());
+    }
+
+    let mut listed = vec![];
+    let mut receiver_gone = false;
+    for entry in source.entries()? {
+        let (name, file_type) = entry?;
+        if cache.is_some() || seen.is_some() {
+            listed.push((name.clone(), file_type));
+        }
+        if !receiver_gone {
+            receiver_gone = !send_entry(name, file_type)?;
+        } else if cache.is_none() {
+            break;
+        }
+    }
+    if !receiver_gone {
+        send_removed(
+            sender,
+            seen.as_ref(),
+            previous.as_ref(),
+            &listed,
+            &is_hidden,
+        );
+    }
+    if let Some(cache) = cache {
+        // A cache that can't be written just means listing again next time
+        let _ = cache.write(&listed);
+    }
+    Ok(())
+}
+
+/// Tell how many of the `previous` names that aren't hidden are gone from the
+/// complete listing, and remember it for next time.
+fn send_removed(
+    sender: &mpsc::Sender<Message>,
+    seen: Option<&Seen>,
+    previous: Option<&HashSet<OsString>>,
+    listed: &[(OsString, FileType)],
+    is_hidden: &dyn Fn(&OsStr, bool) -> bool,
+) {
+    let Some(seen) = seen else {
+        return;
+    };
+    let names: HashSet<&OsString> = listed.iter().map(|(name, _)| name).collect();
+    if let Some(previous) = previous {
+        let removed: Vec<_> = previous
+            .iter()
+            .filter(|name| !names.contains(name))
+            .collect();
+        // Whether it was a directory isn't remembered
+        let shown_removed = removed
+            .iter()
+            .filter(|name| !is_hidden(name, false))
+            .count();
+        let _ = sender.send(Message::Removed(shown_removed));
+        if removed.is_empty() && names.len() == previous.len() {
+            return;
+        }
+    }
+    // Not remembering it just means comparing with an older listing next time
+    let _ = seen.write(names);
+}
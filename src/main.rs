@@ -1,192 +1,432 @@
-use clap::Parser;
-use console::{set_colors_enabled, Style};
-use std::fs;
-use std::io;
-use std::os::fd::AsRawFd;
-use std::os::unix::fs::FileTypeExt;
-use std::time::Instant;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use console::set_colors_enabled;
+use ls_preview::{
+    default_cache_dir, default_state_dir, render_tree, terminal_width, ArchiveSource, Collation,
+    Format, GitRevSource, Layout, LsColors, NameFilter, Preview, Renderer, SortBy,
+};
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
 
+mod config;
+mod init;
 
 #[derive(Parser)]
-#[command(author, version, about = "Show a preview of the directory contents.")]
+#[command(
+    author,
+    version,
+    about = "Show a preview of the directory contents.",
+    after_help = "Defaults are read from $XDG_CONFIG_HOME/ls-preview/config.toml and LS_PREVIEW_OPTS.\n\
+                  Switches are turned off again with --no-<switch>, and --no-git and \
+                  --no-permissions with --git and --permissions."
+)]
+#[command(args_override_self = true)]
 struct Args {
-    /// Maximum number of lines to display
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Maximum number of lines to display per directory
     #[arg(short = 'l', long, default_value_t = 2)]
     max_lines: usize,
 
-    /// Directory to list
+    /// Maximum number of lines to display across all directories,
+    /// not counting headers. Each directory gets at least one line.
+    #[arg(short = 't', long)]
+    total_lines: Option<usize>,
+
+    /// Width to lay the preview out in, instead of detecting the terminal's
+    #[arg(short = 'w', long, value_parser = clap::value_parser!(u16).range(1..))]
+    width: Option<u16>,
+
+    /// Show subdirectories nested under their parent, up to this many levels deep
+    #[arg(short = 'd', long, default_value_t = 0)]
+    depth: usize,
+
+    /// List entries down the columns
+    #[arg(short = 'C', long, overrides_with = "across", alias = "no-across")]
+    down: bool,
+
+    /// List entries across the rows, the default
+    #[arg(short = 'x', long, overrides_with = "down", alias = "no-down")]
+    across: bool,
+
+    /// Order of the entries
+    #[arg(short = 's', long, value_enum, default_value_t = SortBy::Name)]
+    sort: SortBy,
+
+    /// How names are compared
+    #[arg(long, value_enum, default_value_t = Collation::Bytes)]
+    collate: Collation,
+
+    /// Reverse the order
+    #[arg(short = 'r', long, overrides_with = "no_reverse")]
+    reverse: bool,
+
+    #[arg(long, overrides_with = "reverse", hide = true)]
+    no_reverse: bool,
+
+    /// Show directories before other entries
+    #[arg(long, overrides_with = "no_dirs_first")]
+    dirs_first: bool,
+
+    #[arg(long, overrides_with = "dirs_first", hide = true)]
+    no_dirs_first: bool,
+
+    /// Include entries whose names start with a dot
+    #[arg(short = 'a', long, overrides_with = "no_all")]
+    all: bool,
+
+    #[arg(long, overrides_with = "all", hide = true)]
+    no_all: bool,
+
+    /// Hide entries matched by .gitignore, .ignore and the global git excludes
+    #[arg(long, overrides_with = "no_respect_ignore")]
+    respect_ignore: bool,
+
+    #[arg(long, overrides_with = "respect_ignore", hide = true)]
+    no_respect_ignore: bool,
+
+    /// Show only entries matching this glob, can be repeated
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Don't show entries matching this glob, can be repeated
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Show only entries whose names match this regex
+    #[arg(long = "match", value_name = "REGEX")]
+    pattern: Option<String>,
+
+    /// Output format
+    #[arg(short = 'f', long, value_enum, default_value_t = Format::Columns)]
+    format: Format,
+
+    /// Classify symlinks by what they point to
+    #[arg(short = 'L', long, overrides_with = "no_dereference")]
+    dereference: bool,
+
+    #[arg(long, overrides_with = "dereference", hide = true)]
+    no_dereference: bool,
+
+    /// Show where symlinks point when there is space
+    #[arg(long, overrides_with = "no_link_targets")]
+    link_targets: bool,
+
+    #[arg(long, overrides_with = "link_targets", hide = true)]
+    no_link_targets: bool,
+
+    /// Show Nerd Font icons before the names
+    #[arg(long, overrides_with = "no_icons")]
+    icons: bool,
+
+    #[arg(long, overrides_with = "icons", hide = true)]
+    no_icons: bool,
+
+    /// Don't look up mode bits to mark executables and permissions, saving a stat per entry
+    #[arg(long, overrides_with = "permissions")]
+    no_permissions: bool,
+
+    #[arg(long, overrides_with = "no_permissions", hide = true)]
+    permissions: bool,
+
+    /// Don't show git status markers
+    #[arg(long, overrides_with = "git")]
+    no_git: bool,
+
+    #[arg(long, overrides_with = "no_git", hide = true)]
+    git: bool,
+
+    /// Use the named profile from the config file
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// When to use colors, auto honors NO_COLOR, CLICOLOR, CLICOLOR_FORCE and TERM=dumb
+    #[arg(long, value_enum, default_value_t = ColorWhen::Auto)]
+    color: ColorWhen,
+
+    /// Styles in LS_COLORS syntax, used instead of LS_COLORS
+    #[arg(long)]
+    colors: Option<String>,
+
+    /// Widest name shown in full, longer ones are shortened in the middle. 0 for no limit
+    #[arg(long, default_value_t = ls_preview::MAX_NAME_WIDTH)]
+    max_name_width: u16,
+
+    /// Narrowest column assumed when estimating how many entries can fit
+    #[arg(long, default_value_t = ls_preview::MIN_TAB_WIDTH)]
+    min_tab_width: u16,
+
+    /// Time budget in milliseconds for previewing each directory
+    #[arg(long, default_value_t = ls_preview::TIME_LIMIT.as_millis() as u64)]
+    time_limit: u64,
+
+    /// Cache complete listings under $XDG_CACHE_HOME/ls-preview and refresh
+    /// incomplete ones in the background
+    #[arg(long, overrides_with = "no_cache")]
+    cache: bool,
+
+    #[arg(long, overrides_with = "cache", hide = true)]
+    no_cache: bool,
+
+    /// Highlight entries that are new since the directory was last previewed
+    /// and tell how many were removed, remembered under
+    /// $XDG_STATE_HOME/ls-preview
+    #[arg(long, overrides_with = "no_changes")]
+    changes: bool,
+
+    #[arg(long, overrides_with = "changes", hide = true)]
+    no_changes: bool,
+
+    /// List the directory completely into the cache, used by --cache
+    #[arg(long, hide = true)]
+    refresh_cache: Option<PathBuf>,
+
+    /// Directory inside each archive, or each directory, to list instead
+    #[arg(long, value_name = "PATH")]
+    path: Option<PathBuf>,
+
+    /// List each directory as it is at the git commit-ish REV, marking entries
+    /// added (A), deleted (D) or changed (M) since then
+    #[arg(long, value_name = "REV")]
+    rev: Option<String>,
+
+    /// Directories or archives to list, prefix with ./ to list a directory named like a subcommand
     #[arg(default_value = ".")]
-    directory_path: String,
+    directory_paths: Vec<String>,
 }
 
-const MIN_TAB_WIDTH: u16 = 8;
-const TIME_LIMIT_NS: u128 = 10_000_000; // 10ms
-
-fn main() -> std::io::Result<()> {
-    set_colors_enabled(true); // Force color output even when piping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ColorWhen {
+    /// Only when writing to a terminal
+    Auto,
+    Always,
+    Never,
+}
 
-    let args = Args::parse();
-    let max_lines = args.max_lines;
-    if max_lines == 0 {
-        eprintln!("Error: `max_lines` must be greater than 0");
-        std::process::exit(1);
+impl ColorWhen {
+    fn enabled(self) -> bool {
+        let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+        match self {
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+            // See https://no-color.org and https://bixense.com/clicolors
+            ColorWhen::Auto if var("CLICOLOR_FORCE").is_some_and(|value| value != "0") => true,
+            ColorWhen::Auto if var("NO_COLOR").is_some() => false,
+            ColorWhen::Auto if var("CLICOLOR").is_some_and(|value| value == "0") => false,
+            ColorWhen::Auto if var("TERM").is_some_and(|value| value == "dumb") => false,
+            ColorWhen::Auto => io::stdout().is_terminal(),
+        }
     }
+}
 
-    let terminal_width = get_terminal_width();
-    let max_columns = terminal_width
-        .map(|width| (width / MIN_TAB_WIDTH) as usize)
-        .unwrap_or(0)
-        .max(1);
-
-    let max_items = max_columns * max_lines;
-
-    let mut times = vec![];
-    let mut entries = vec![];
-    let mut now = Instant::now();
-    let mut num_dirs = 0;
-    let mut too_many_dirs = false;
-    let mut ran_out_of_time_listing = false;
-
-    let mut i = 0;
-    for entry in fs::read_dir(&args.directory_path)? {
-        let entry_ref = entry.as_ref().unwrap();
-        let name = entry_ref.file_name();
-        if name.to_string_lossy().starts_with(".") {
-            continue;
-        }
-        let meta = entry_ref.file_type()?;
-        if meta.is_dir() {
-            num_dirs += 1;
+#[derive(Subcommand)]
+enum Command {
+    /// Print a shell hook that previews the directory after every `cd`
+    ///
+    /// For example, add `eval "$(ls-preview init zsh --profile prompt)"` to ~/.zshrc.
+    Init {
+        shell: init::Shell,
+
+        /// Flags passed to ls-preview by the hook
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Print shell completions
+    Completions { shell: init::Shell },
+}
+
+fn main() -> std::io::Result<()> {
+    // For `--collate locale`, set while no other thread is running
+    unsafe {
+        libc::setlocale(libc::LC_COLLATE, c"".as_ptr());
+    }
+    // Defaults only apply to previews
+    let is_subcommand = std::env::args_os().nth(1).is_some_and(|arg| {
+        Args::command()
+            .get_subcommands()
+            .any(|subcommand| arg == subcommand.get_name())
+    });
+    let args = match config::args() {
+        _ if is_subcommand => Args::parse(),
+        Ok(args) => Args::parse_from(args),
+        Err(err) => {
+            eprintln!("Error: {err}");
+            std::process::exit(1);
         }
-        entries.push((name, meta));
-        if num_dirs >= max_items {
-            too_many_dirs = true;
-            break;
+    };
+    set_colors_enabled(args.color.enabled());
+
+    match args.command {
+        Some(Command::Init { shell, args }) => {
+            print!("{}", init::hook(shell, &args));
+            return Ok(());
         }
-        let elapsed = now.elapsed().as_nanos();
-        now = Instant::now();
-        times.push((i, elapsed));
-        if elapsed > TIME_LIMIT_NS {
-            ran_out_of_time_listing = true;
-            break;
+        Some(Command::Completions { shell }) => {
+            print!("{}", init::completions(shell, &mut Args::command()));
+            return Ok(());
         }
-        i += 1;
+        None => {}
     }
 
-    let must_print_dirs = ran_out_of_time_listing || too_many_dirs || entries.len() > max_items;
-
-    // Collect and sort entries by name
-    let entries: Vec<_> = if must_print_dirs {
-        entries
-            .iter()
-            .filter(|(_, meta)| meta.is_dir())
-            .map(|(name, meta)| {
-                let width = console::measure_text_width(&name.to_string_lossy()) as u16;
-                (name, meta, width)
-            })
-            .collect()
+    let cache_dir = if args.cache || args.refresh_cache.is_some() {
+        default_cache_dir()
     } else {
-        entries
-            .iter()
-            .map(|(name, meta)| {
-                let width = console::measure_text_width(&name.to_string_lossy()) as u16;
-                (name, meta, width)
-            })
-            .collect()
+        None
     };
-
-    if entries.is_empty() {
+    if let Some(directory) = &args.refresh_cache {
+        if let Some(cache_dir) = &cache_dir {
+            ls_preview::refresh_cache(cache_dir, directory)?;
+        }
         return Ok(());
     }
+    let state_dir = if args.changes {
+        default_state_dir()
+    } else {
+        None
+    };
 
-    print_entries_in_columns(entries, terminal_width, max_lines, !must_print_dirs)?;
+    if args.max_lines == 0 {
+        eprintln!("Error: `max_lines` must be greater than 0");
+        std::process::exit(1);
+    }
 
-    Ok(())
-}
+    if args.depth > 0 && args.format != Format::Columns {
+        eprintln!("Error: `depth` only works with the columns format");
+        std::process::exit(1);
+    }
 
-fn print_entries_in_columns(
-    mut entries: Vec<(&std::ffi::OsString, &std::fs::FileType, u16)>,
-    terminal_width: Option<u16>,
-    max_lines: usize,
-    consider_dirs_only: bool,
-) -> io::Result<()> {
-    // Find the maximum width needed
-    let max_width = entries.iter().map(|(_, _, width)| *width).max().unwrap();
-    
-    let num_columns = std::cmp::max(1, terminal_width.map(|w| w / (max_width + 2)).unwrap_or(0));
-    // Doesn't matter if we don't know terminal_width
-    let column_width = terminal_width.map(|w| w / num_columns).unwrap_or(0);
-
-    if consider_dirs_only {
-        let max_items = (num_columns as usize) * max_lines;
-        if entries.len() > max_items {
-            let is_dir = |(_, meta, _): &(_, &std::fs::FileType, _)| meta.is_dir();
-            if entries.iter().any(is_dir) {
-                return print_entries_in_columns(
-                    entries.into_iter().filter(is_dir).collect(),
-                    terminal_width,
-                    max_lines,
-                    false
-                );
-            }
+    let name_filter = match NameFilter::new(&args.include, &args.exclude, args.pattern.as_deref()) {
+        Ok(name_filter) => name_filter,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            std::process::exit(1);
         }
-    }
+    };
 
-    entries.sort_by(|a, b| a.0.cmp(b.0));
+    // Like ls, assume a common terminal when output goes nowhere that has a width
+    let width = args
+        .width
+        .or_else(terminal_width)
+        .unwrap_or(ls_preview::DEFAULT_WIDTH);
+    let ls_colors = args
+        .colors
+        .as_deref()
+        .map(LsColors::parse)
+        .or_else(LsColors::from_env);
+    let needs_metadata = ls_colors.as_ref().is_some_and(LsColors::needs_metadata);
+    let renderer = Renderer::new().ls_colors(ls_colors).format(args.format);
+    let show_headers = args.directory_paths.len() > 1 && args.format == Format::Columns;
+    // Fill at most the fzf preview window
+    let mut remaining_lines = args.total_lines.or_else(|| {
+        std::env::var("FZF_PREVIEW_LINES")
+            .ok()
+            .and_then(|lines| lines.trim().parse().ok())
+    });
+    let mut failed = false;
+    let mut printed_any = false;
 
-    // Print entries in columns
-    for (i, (name, file_type, width)) in entries.iter().enumerate() {
-        if i == max_lines * (num_columns as usize) - 1 {
-            println!("....");
-            break;
-        }
+    let mut stdout = io::stdout().lock();
+    for (i, directory_path) in args.directory_paths.iter().enumerate() {
+        // Split what's left evenly, so lines unused by earlier directories go to later ones
+        let remaining_directories = args.directory_paths.len() - i;
+        let max_lines = remaining_lines
+            .map(|lines| (lines / remaining_directories).clamp(1, args.max_lines))
+            .unwrap_or(args.max_lines);
 
-        let (style, indicator) = get_color_and_indicator(file_type);
-        print!("{}{}", style.apply_to(name.to_string_lossy()), indicator);
+        let archive = ArchiveSource::new(directory_path)
+            .map(|archive| archive.path(args.path.as_deref().unwrap_or(Path::new(""))));
+        let directory = match &args.path {
+            Some(path) => Path::new(directory_path).join(path),
+            None => PathBuf::from(directory_path),
+        };
+        let mut preview = Preview::new(&directory)
+            .max_lines(max_lines)
+            .width(Some(width))
+            .layout(if args.down {
+                Layout::Down
+            } else {
+                Layout::Across
+            })
+            .max_name_width(args.max_name_width)
+            .min_tab_width(args.min_tab_width)
+            .time_limit(Some(Duration::from_millis(args.time_limit)))
+            .git_status(!args.no_git)
+            .sort_by(args.sort)
+            .collation(args.collate)
+            .reverse(args.reverse)
+            .dirs_first(args.dirs_first)
+            .all(args.all)
+            .respect_ignore(args.respect_ignore)
+            .name_filter(name_filter.clone())
+            .metadata(needs_metadata || !args.no_permissions)
+            .follow_links(args.dereference)
+            .link_targets(args.link_targets)
+            .icons(args.icons)
+            .cache_dir(cache_dir.clone())
+            .state_dir(state_dir.clone());
+        // Only directories on disk are cached
+        let cacheable = archive.is_none() && args.rev.is_none();
+        if let Some(archive) = archive {
+            preview = preview.source(Arc::new(archive));
+        } else if let Some(rev) = &args.rev {
+            preview = preview.source(Arc::new(GitRevSource::new(&directory, rev)));
+        }
+        let mut buffer = vec![];
+        let lines = if args.depth > 0 {
+            render_tree(&mut buffer, &renderer, &preview, args.depth)
+        } else {
+            preview.run().and_then(|listing| {
+                if cacheable && cache_dir.is_some() && !listing.complete && !listing.cached {
+                    spawn_cache_refresh(&directory);
+                }
+                renderer.render(&mut buffer, &listing)?;
+                Ok(listing.lines())
+            })
+        };
+        let lines = match lines {
+            Ok(lines) => lines,
+            Err(err) => {
+                eprintln!("ls-preview: {directory_path}: {err}");
+                failed = true;
+                continue;
+            }
+        };
 
-        // Add padding to align to column width, except for last column
-        let next_row_index = (i as u16 + 1) % num_columns;
-        if next_row_index != 0 {
-            let padding = column_width - *width;
-            print!("{}", " ".repeat(padding as usize));
+        if show_headers {
+            if printed_any {
+                writeln!(stdout)?;
+            }
+            writeln!(stdout, "{directory_path}:")?;
         }
+        stdout.write_all(&buffer)?;
+        printed_any = true;
 
-        // New line after each row
-        if next_row_index == 0 || i == entries.len() - 1 {
-            println!();
+        if let Some(remaining_lines) = &mut remaining_lines {
+            *remaining_lines = remaining_lines.saturating_sub(lines);
         }
     }
-    Ok(())
-}
 
-#[allow(clippy::if_same_then_else)]
-fn get_color_and_indicator(file_type: &fs::FileType) -> (Style, &'static str) {
-    if file_type.is_dir() {
-        (Style::new().blue().bold(), "/")
-    } else if file_type.is_symlink() {
-        (Style::new().magenta(), "@")
-    } else if file_type.is_socket() {
-        (Style::new().magenta(), "=")
-    } else if file_type.is_fifo() {
-        (Style::new().yellow(), "|")
-    } else if file_type.is_block_device() {
-        (Style::new().blue().on_cyan(), "")
-    } else if file_type.is_char_device() {
-        (Style::new().blue().on_yellow(), "")
-    } else if file_type.is_file() {
-        (Style::new(), "")
-    } else {
-        (Style::new(), "")
+    if failed {
+        std::process::exit(1);
     }
+    Ok(())
 }
 
-fn get_terminal_width() -> Option<u16> {
-    let tty = std::fs::File::open("/dev/tty").ok()?;
-    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
-    let result = unsafe { libc::ioctl(tty.as_raw_fd(), libc::TIOCGWINSZ, &mut ws) };
-    if result == 0 && ws.ws_col > 0 {
-        Some(ws.ws_col)
-    } else {
-        None
-    }
+/// Fill the cache for `directory` in a detached process, so this one can exit on time.
+fn spawn_cache_refresh(directory: &Path) {
+    let Ok(exe) = std::env::current_exe() else {
+        return;
+    };
+    let _ = process::Command::new(exe)
+        .arg("--refresh-cache")
+        .arg(directory)
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .process_group(0)
+        .spawn();
 }
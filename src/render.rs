@@ -0,0 +1,201 @@
+use console::Style;
+use std::io::{self, Write};
+
+use crate::format::{self, Format};
+use crate::layout::cell_width;
+use crate::style::{is_inaccessible, new_marker};
+use crate::{get_entry_color_and_indicator, get_git_marker, Entry, Listing, LsColors};
+
+/// Writes a listing in columns or one of the machine-readable formats.
+#[derive(Debug, Clone, Default)]
+pub struct Renderer {
+    ls_colors: Option<LsColors>,
+    format: Format,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Style entries with `LS_COLORS`, falling back to the built-in palette.
+    pub fn ls_colors(mut self, ls_colors: Option<LsColors>) -> Self {
+        self.ls_colors = ls_colors;
+        self
+    }
+
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn render<W: Write>(&self, out: &mut W, listing: &Listing) -> io::Result<()> {
+        match self.format {
+            Format::Columns => self.render_columns(out, listing),
+            Format::Json => format::write_json(out, listing),
+            Format::Ndjson => format::write_ndjson(out, listing),
+            Format::Lines => format::write_names(out, listing, b'\n'),
+            Format::Print0 => format::write_names(out, listing, b'\0'),
+        }
+    }
+
+    fn render_columns<W: Write>(&self, out: &mut W, listing: &Listing) -> io::Result<()> {
+        let entries = &listing.entries;
+
+        if entries.is_empty() && listing.timed_out {
+            return writeln!(
+                out,
+                "{}",
+                Style::new().dim().apply_to("(listing timed out)")
+            );
+        }
+
+        let grid = listing.grid();
+        for row in 0..grid.rows {
+            let mut x = 0;
+            for (column, column_width) in grid.widths.iter().enumerate() {
+                let Some(slot) = grid.slot(row, column) else {
+                    break;
+                };
+                if slot == grid.shown {
+                    // The footer takes the rest of the row
+                    let available = listing.width.map(|width| width.saturating_sub(x));
+                    let footer = footer(listing, available);
+                    write!(out, "{}", Style::new().dim().apply_to(footer))?;
+                    break;
+                }
+
+                let entry = &entries[slot];
+                if let Some(status) = entry.git_status {
+                    let (style, marker) = get_git_marker(status);
+                    write!(out, "{} ", style.apply_to(marker))?;
+                }
+                if let Some(marker) = new_marker(entry) {
+                    write!(out, "{marker} ")?;
+                }
+
+                let (style, indicator) = self.style(entry);
+                if let Some(icon) = entry.icon {
+                    write!(out, "{} ", style.apply_to(icon))?;
+                }
+                write!(out, "{}{}", style.apply_to(entry.display_name()), indicator)?;
+                if let Some(link_path) = &entry.link_path {
+                    write!(
+                        out,
+                        " {} {}",
+                        Style::new().dim().apply_to("→"),
+                        link_path.to_string_lossy()
+                    )?;
+                }
+
+                // Add padding to align to column width, except for last column
+                if grid.slot(row, column + 1).is_some() {
+                    let padding = column_width - cell_width(entry);
+                    write!(out, "{}", " ".repeat(padding as usize))?;
+                }
+                x += column_width;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Style and indicator for an entry.
+    pub(crate) fn style(&self, entry: &Entry) -> (Style, &'static str) {
+        let (default_style, indicator) = get_entry_color_and_indicator(entry);
+        let style = match self
+            .ls_colors
+            .as_ref()
+            .and_then(|colors| colors.style(entry))
+        {
+            Some(style) if is_inaccessible(entry) => style.dim(),
+            Some(style) => style,
+            None => default_style,
+        };
+        // New since the last preview
+        let style = if entry.is_new {
+            style.bold().underlined()
+        } else {
+            style
+        };
+        (style, indicator)
+    }
+}
+
+/// Summary of what was left out and what was removed since the last preview,
+/// shortened until it fits in `available` columns.
+fn footer(listing: &Listing, available: Option<u16>) -> String {
+    let omitted = listing.entries[listing.shown()..]
+        .iter()
+        .chain(&listing.dropped);
+    let (dirs, files) = omitted.fold((0, 0), |(dirs, files), entry| {
+        if entry.file_type.is_dir() {
+            (dirs + 1, files)
+        } else {
+            (dirs, files + 1)
+        }
+    });
+
+    let mut counts = vec![];
+    if files > 0 {
+        counts.push(plural(files, "file", "files"));
+    }
+    if dirs > 0 {
+        counts.push(plural(dirs, "dir", "dirs"));
+    }
+    if listing.hidden > 0 {
+        counts.push(format!("{} hidden", listing.hidden));
+    }
+    // Stopped early, there are more than counted
+    let cut_short = if listing.timed_out {
+        Some("listing timed out")
+    } else if !listing.complete {
+        Some("not all listed")
+    } else {
+        None
+    };
+
+    let mut full = String::new();
+    // Otherwise the footer is only there to tell about removed entries
+    if listing.omitted() > 0 || cut_short.is_some() {
+        full += "…";
+        if !counts.is_empty() {
+            full += &format!(" {}", counts.join(", "));
+        }
+        if let Some(reason) = cut_short {
+            full += &format!(" ({reason})");
+        }
+    }
+    let mut short = match (listing.omitted(), listing.timed_out) {
+        (0, true) => "… (timed out)".to_string(),
+        (0, false) if cut_short.is_some() => "…".to_string(),
+        (0, false) => String::new(),
+        // Only a lower bound when not all were listed
+        (omitted, false) if !listing.complete => format!("… +{omitted}+"),
+        (omitted, false) => format!("… +{omitted}"),
+        (omitted, true) => format!("… +{omitted} (timed out)"),
+    };
+    if listing.removed > 0 {
+        full += &format!(" −{} removed", listing.removed);
+        short += &format!(" −{}", listing.removed);
+    }
+    let (full, short) = (
+        full.trim_start().to_string(),
+        short.trim_start().to_string(),
+    );
+    [full, short]
+        .into_iter()
+        .find(|footer| {
+            available.is_none_or(|available| footer.chars().count() < available as usize)
+        })
+        .unwrap_or_else(|| "…".to_string())
+}
+
+fn plural(count: usize, one: &str, many: &str) -> String {
+    format!("+{count} {}", if count == 1 { one } else { many })
+}
+
+/// Write the listing in columns with the built-in palette.
+pub fn render<W: Write>(out: &mut W, listing: &Listing) -> io::Result<()> {
+    Renderer::new().render(out, listing)
+}
@@ -0,0 +1,168 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, OnceLock};
+
+use crate::source::{DirSource, Entries};
+use crate::{FileType, GitStatus};
+
+/// A directory as it is in a git revision, with entries marked by how the
+/// revision differs from the work tree.
+///
+/// Entries are marked like `git diff <rev>` would: added if only in the work
+/// tree, deleted if only in the revision. Added entries are listed too, so
+/// nothing in either goes unnoticed.
+#[derive(Debug, Clone)]
+pub struct GitRevSource {
+    directory: PathBuf,
+    rev: String,
+    listed: OnceLock<Listed>,
+}
+
+#[derive(Debug, Clone)]
+struct Listed {
+    entries: Vec<(OsString, FileType)>,
+    statuses: HashMap<OsString, GitStatus>,
+}
+
+impl GitRevSource {
+    /// `directory` in the work tree, at the commit-ish `rev`.
+    pub fn new(directory: impl Into<PathBuf>, rev: impl Into<String>) -> Self {
+        GitRevSource {
+            directory: directory.into(),
+            rev: rev.into(),
+            listed: OnceLock::new(),
+        }
+    }
+
+    fn listed(&self) -> io::Result<&Listed> {
+        if let Some(listed) = self.listed.get() {
+            return Ok(listed);
+        }
+        let listed = self.list()?;
+        Ok(self.listed.get_or_init(|| listed))
+    }
+
+    fn list(&self) -> io::Result<Listed> {
+        // The directory may only be in the revision, so git runs in the
+        // closest one that's in the work tree, `-C ""` being the current one
+        let work_dir = self
+            .directory
+            .ancestors()
+            .find(|dir| dir.is_dir())
+            .unwrap_or(Path::new(""));
+        let prefix = self
+            .directory
+            .strip_prefix(work_dir)
+            .unwrap_or(&self.directory);
+        let pathspec = Path::new(".").join(prefix).join("");
+        // Paths git prints are relative to `work_dir`, the entry is the
+        // component after `prefix`
+        let entry_name = |path: &Path| {
+            let first = path.strip_prefix(prefix).ok()?.components().next()?;
+            Some(first.as_os_str().to_os_string())
+        };
+
+        let tree = git(work_dir, &["ls-tree", "-z", &self.rev], &pathspec)?;
+        let mut entries: Vec<_> = tree
+            .split(|&byte| byte == 0)
+            .filter_map(parse_tree_record)
+            .filter_map(|(path, file_type)| Some((entry_name(path)?, file_type)))
+            .collect();
+        if entries.is_empty() && work_dir != self.directory {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No such directory in the revision or the work tree",
+            ));
+        }
+        // Paths that differ between the revision and the work tree, untracked
+        // files aren't part of the diff
+        let diff = git(
+            work_dir,
+            &["diff", "--name-only", "-z", "--relative", &self.rev],
+            &pathspec,
+        )?;
+        let untracked = git(
+            work_dir,
+            &["ls-files", "-z", "--others", "--exclude-standard"],
+            &pathspec,
+        )?;
+        let changed: HashSet<OsString> = diff
+            .split(|&byte| byte == 0)
+            .chain(untracked.split(|&byte| byte == 0))
+            .filter_map(|path| entry_name(Path::new(OsStr::from_bytes(path))))
+            .collect();
+
+        let at_rev: HashSet<OsString> = entries.iter().map(|(name, _)| name.clone()).collect();
+        let mut statuses = HashMap::new();
+        for name in &changed {
+            let in_work_tree = fs::symlink_metadata(self.directory.join(name));
+            let status = match (at_rev.contains(name), &in_work_tree) {
+                (true, Ok(_)) => GitStatus::Modified,
+                (true, Err(_)) => GitStatus::Deleted,
+                (false, Ok(metadata)) => {
+                    entries.push((name.clone(), FileType::from(metadata.file_type())));
+                    GitStatus::Added
+                }
+                (false, Err(_)) => continue,
+            };
+            statuses.insert(name.clone(), status);
+        }
+        Ok(Listed { entries, statuses })
+    }
+}
+
+impl DirSource for GitRevSource {
+    fn entries(&self) -> io::Result<Entries<'_>> {
+        Ok(Box::new(self.listed()?.entries.iter().cloned().map(Ok)))
+    }
+
+    fn git_status(&self, name: &OsStr) -> Option<GitStatus> {
+        self.listed().ok()?.statuses.get(name).copied()
+    }
+
+    fn open(&self, name: &OsStr) -> io::Result<Arc<dyn DirSource>> {
+        Ok(Arc::new(GitRevSource::new(
+            self.directory.join(name),
+            self.rev.clone(),
+        )))
+    }
+}
+
+/// Run git in `work_dir` on the paths matching `pathspec`, failing with its
+/// message if it does.
+fn git(work_dir: &Path, args: &[&str], pathspec: &Path) -> io::Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(work_dir)
+        .args(args)
+        .arg("--")
+        .arg(pathspec)
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        let message = message.trim().trim_start_matches("fatal: ");
+        return Err(io::Error::other(message.to_string()));
+    }
+    Ok(output.stdout)
+}
+
+/// `<mode> <type> <object>\t<path>`, `None` if malformed.
+fn parse_tree_record(record: &[u8]) -> Option<(&Path, FileType)> {
+    let tab = record.iter().position(|&byte| byte == b'\t')?;
+    let mode = record.split(|&byte| byte == b' ').next()?;
+    let file_type = match mode {
+        // Submodules are shown as the directories they're checked out in
+        b"040000" | b"160000" => FileType::Dir,
+        b"120000" => FileType::Symlink,
+        _ => FileType::File,
+    };
+    let path = Path::new(OsStr::from_bytes(&record[tab + 1..]));
+    Some((path, file_type))
+}
@@ -0,0 +1,48 @@
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+
+use crate::store::KeyedFile;
+
+/// The names listed the last time a directory was previewed completely.
+pub(crate) struct Seen {
+    file: KeyedFile,
+}
+
+impl Seen {
+    pub(crate) fn open(state_dir: &Path, directory: &Path) -> Option<Self> {
+        let metadata = fs::metadata(directory).ok()?;
+        Some(Seen {
+            file: KeyedFile::new(&state_dir.join("seen"), "seen", &metadata),
+        })
+    }
+
+    /// The names seen last time, `None` the first time.
+    pub(crate) fn read(&self) -> Option<HashSet<OsString>> {
+        let contents = self.file.read()?;
+        Some(
+            contents
+                .split(|&byte| byte == 0)
+                .filter(|name| !name.is_empty())
+                .map(|name| OsString::from_vec(name.to_vec()))
+                .collect(),
+        )
+    }
+
+    /// Remember `names`, replacing the old ones atomically.
+    pub(crate) fn write<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a OsString>,
+    ) -> io::Result<()> {
+        self.file.write(|file| {
+            for name in names {
+                file.write_all(name.as_bytes())?;
+                file.write_all(&[0])?;
+            }
+            Ok(())
+        })
+    }
+}
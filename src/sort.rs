@@ -0,0 +1,212 @@
+use std::cmp::Ordering;
+use std::ffi::{CString, OsStr};
+use std::os::unix::ffi::OsStrExt;
+
+use crate::Entry;
+
+/// Order in which entries are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SortBy {
+    /// By name
+    #[default]
+    Name,
+    /// By name, with runs of digits compared as numbers
+    Natural,
+    /// Newest first
+    Mtime,
+    /// Largest first
+    Size,
+    /// By extension, then by name
+    Extension,
+    /// In directory order
+    None,
+}
+
+/// How names are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Collation {
+    /// Byte-wise
+    #[default]
+    Bytes,
+    /// Ignoring case
+    IgnoreCase,
+    /// According to `LC_COLLATE`
+    Locale,
+}
+
+impl SortBy {
+    /// Whether sorting needs the entries' metadata.
+    pub(crate) fn needs_metadata(self) -> bool {
+        matches!(self, SortBy::Mtime | SortBy::Size)
+    }
+}
+
+pub(crate) fn sort_entries(
+    entries: &mut [Entry],
+    sort_by: SortBy,
+    collation: Collation,
+    reverse: bool,
+    dirs_first: bool,
+) {
+    if sort_by != SortBy::None {
+        let by_name = |a: &Entry, b: &Entry| collate(&a.name, &b.name, collation);
+        entries.sort_by(|a, b| match sort_by {
+            SortBy::Name | SortBy::None => by_name(a, b),
+            SortBy::Natural => natural(&a.name, &b.name, collation),
+            SortBy::Mtime => {
+                let modified = |entry: &Entry| {
+                    entry
+                        .metadata
+                        .as_ref()
+                        .and_then(|metadata| metadata.modified().ok())
+                };
+                modified(b).cmp(&modified(a)).then_with(|| by_name(a, b))
+            }
+            SortBy::Size => {
+                let size = |entry: &Entry| entry.metadata.as_ref().map(|metadata| metadata.len());
+                size(b).cmp(&size(a)).then_with(|| by_name(a, b))
+            }
+            SortBy::Extension => collate(extension(&a.name), extension(&b.name), collation)
+                .then_with(|| by_name(a, b)),
+        });
+    }
+    if reverse {
+        entries.reverse();
+    }
+    if dirs_first {
+        // Stable, so the order within each group is kept
+        entries.sort_by_key(|entry| !entry.file_type.is_dir());
+    }
+}
+
+/// Extension without the dot, empty for none and for dotfiles.
+fn extension(name: &OsStr) -> &OsStr {
+    let bytes = name.as_bytes();
+    match bytes.iter().rposition(|&byte| byte == b'.') {
+        Some(0) | None => OsStr::new(""),
+        Some(dot) => OsStr::from_bytes(&bytes[dot + 1..]),
+    }
+}
+
+fn collate(a: &OsStr, b: &OsStr, collation: Collation) -> Ordering {
+    collate_loosely(a, b, collation).then_with(|| a.cmp(b))
+}
+
+/// Like `collate`, but names differing only in case or by locale are equal.
+fn collate_loosely(a: &OsStr, b: &OsStr, collation: Collation) -> Ordering {
+    match collation {
+        Collation::Bytes => a.cmp(b),
+        Collation::IgnoreCase => a
+            .to_string_lossy()
+            .to_lowercase()
+            .cmp(&b.to_string_lossy().to_lowercase()),
+        Collation::Locale => strcoll(a, b),
+    }
+}
+
+fn strcoll(a: &OsStr, b: &OsStr) -> Ordering {
+    // File names can't contain NUL
+    let (Ok(a), Ok(b)) = (CString::new(a.as_bytes()), CString::new(b.as_bytes())) else {
+        return Ordering::Equal;
+    };
+    unsafe { libc::strcoll(a.as_ptr(), b.as_ptr()) }.cmp(&0)
+}
+
+/// Compare with runs of digits as numbers, so `file2` comes before `file10`.
+fn natural(a: &OsStr, b: &OsStr, collation: Collation) -> Ordering {
+    let (mut a_chunks, mut b_chunks) = (chunks(a.as_bytes()), chunks(b.as_bytes()));
+    loop {
+        let ordering = match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return collate(a, b, collation),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) if a[0].is_ascii_digit() && b[0].is_ascii_digit() => {
+                let trim = |digits: &[u8]| {
+                    let start = digits
+                        .iter()
+                        .position(|&d| d != b'0')
+                        .unwrap_or(digits.len());
+                    digits[start..].to_vec()
+                };
+                let (a, b) = (trim(a), trim(b));
+                a.len().cmp(&b.len()).then_with(|| a.cmp(&b))
+            }
+            // Case and locale only break ties once the whole names are compared
+            (Some(a), Some(b)) => {
+                collate_loosely(OsStr::from_bytes(a), OsStr::from_bytes(b), collation)
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// Split into alternating runs of digits and non-digits.
+fn chunks(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = bytes;
+    std::iter::from_fn(move || {
+        let first = rest.first()?;
+        let len = rest
+            .iter()
+            .position(|byte| byte.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(len);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str], collation: Collation) -> Vec<String> {
+        let mut names: Vec<&OsStr> = names.iter().map(OsStr::new).collect();
+        names.sort_by(|a, b| natural(a, b, collation));
+        names
+            .iter()
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(
+            natural(OsStr::new("file2"), OsStr::new("file10"), Collation::Bytes),
+            Ordering::Less
+        );
+        assert_eq!(
+            sorted(&["v1.10.0", "v1.9.2", "v1.9.10", "v10"], Collation::Bytes),
+            ["v1.9.2", "v1.9.10", "v1.10.0", "v10"]
+        );
+    }
+
+    #[test]
+    fn leading_zeros_only_break_ties() {
+        assert_eq!(
+            sorted(
+                &["file10", "file2", "file1", "file01", "file007", "file7"],
+                Collation::Bytes
+            ),
+            ["file01", "file1", "file2", "file007", "file7", "file10"]
+        );
+        assert_eq!(
+            sorted(&["img0000", "img00", "img0"], Collation::Bytes),
+            ["img0", "img00", "img0000"]
+        );
+    }
+
+    #[test]
+    fn text_runs_follow_collation() {
+        assert_eq!(
+            sorted(&["b2", "B10", "a1", "A3"], Collation::Bytes),
+            ["A3", "B10", "a1", "b2"]
+        );
+        assert_eq!(
+            sorted(&["b2", "B10", "a1", "A3"], Collation::IgnoreCase),
+            ["a1", "A3", "b2", "B10"]
+        );
+        assert_eq!(sorted(&["a1", "a"], Collation::Bytes), ["a", "a1"]);
+    }
+}
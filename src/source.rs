@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{FileType, GitStatus, LinkTarget};
+
+/// Names and types of entries, in the order the source yields them.
+pub type Entries<'a> = Box<dyn Iterator<Item = io::Result<(OsString, FileType)>> + 'a>;
+
+/// Where the entries of a preview come from, the filesystem by default.
+///
+/// Only the filesystem is cached, gets git status and honors ignore files.
+pub trait DirSource: fmt::Debug + Send + Sync {
+    /// The entries, in no particular order.
+    fn entries(&self) -> io::Result<Entries<'_>>;
+
+    /// Metadata of an entry without following symlinks, `None` if the source
+    /// has none.
+    fn metadata(&self, _name: &OsStr) -> io::Result<Option<fs::Metadata>> {
+        Ok(None)
+    }
+
+    /// What the symlink `name` points to, `None` if the source can't tell.
+    fn link_target(&self, _name: &OsStr) -> Option<LinkTarget> {
+        None
+    }
+
+    /// The path the symlink `name` contains.
+    fn read_link(&self, _name: &OsStr) -> Option<PathBuf> {
+        None
+    }
+
+    /// How the entry `name` differs from the work tree, for sources that
+    /// compare against one.
+    fn git_status(&self, _name: &OsStr) -> Option<GitStatus> {
+        None
+    }
+
+    /// The source of the subdirectory `name`, used for nested previews.
+    fn open(&self, _name: &OsStr) -> io::Result<Arc<dyn DirSource>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "source has no subdirectories",
+        ))
+    }
+}
+
+/// A directory on the filesystem.
+#[derive(Debug, Clone)]
+pub struct FsSource {
+    directory: PathBuf,
+}
+
+impl FsSource {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        FsSource {
+            directory: directory.into(),
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+}
+
+impl DirSource for FsSource {
+    fn entries(&self) -> io::Result<Entries<'_>> {
+        let read_dir = fs::read_dir(&self.directory)?;
+        Ok(Box::new(read_dir.map(|entry| {
+            let entry = entry?;
+            Ok((entry.file_name(), FileType::from(entry.file_type()?)))
+        })))
+    }
+
+    fn metadata(&self, name: &OsStr) -> io::Result<Option<fs::Metadata>> {
+        fs::symlink_metadata(self.directory.join(name)).map(Some)
+    }
+
+    fn link_target(&self, name: &OsStr) -> Option<LinkTarget> {
+        Some(match fs::metadata(self.directory.join(name)) {
+            Ok(target) => LinkTarget::Found(target),
+            Err(err) if err.raw_os_error() == Some(libc::ELOOP) => LinkTarget::Loop,
+            Err(_) => LinkTarget::Broken,
+        })
+    }
+
+    fn read_link(&self, name: &OsStr) -> Option<PathBuf> {
+        fs::read_link(self.directory.join(name)).ok()
+    }
+
+    fn open(&self, name: &OsStr) -> io::Result<Arc<dyn DirSource>> {
+        Ok(Arc::new(FsSource::new(self.directory.join(name))))
+    }
+}
+
+/// Entries held in memory, e.g. to preview a synthetic directory of any size.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    entries: Vec<(OsString, FileType)>,
+    subdirectories: HashMap<OsString, Arc<MemorySource>>,
+}
+
+impl MemorySource {
+    pub fn new(entries: impl IntoIterator<Item = (impl Into<OsString>, FileType)>) -> Self {
+        MemorySource {
+            entries: entries
+                .into_iter()
+                .map(|(name, file_type)| (name.into(), file_type))
+                .collect(),
+            subdirectories: HashMap::new(),
+        }
+    }
+
+    /// Add the directory `name` with the entries of `source`, for nested previews.
+    pub fn subdirectory(mut self, name: impl Into<OsString>, source: MemorySource) -> Self {
+        let name = name.into();
+        self.entries.push((name.clone(), FileType::Dir));
+        self.subdirectories.insert(name, Arc::new(source));
+        self
+    }
+}
+
+impl DirSource for MemorySource {
+    fn entries(&self) -> io::Result<Entries<'_>> {
+        Ok(Box::new(self.entries.iter().cloned().map(Ok)))
+    }
+
+    fn open(&self, name: &OsStr) -> io::Result<Arc<dyn DirSource>> {
+        match self.subdirectories.get(name) {
+            Some(source) => Ok(source.clone()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+}
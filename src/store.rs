@@ -0,0 +1,82 @@
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Bumped when the format of the stored files changes, so old ones are ignored.
+const VERSION: u32 = 1;
+
+/// `$XDG_CACHE_HOME/ls-preview`, or under `~/.cache`.
+pub fn default_cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// `$XDG_STATE_HOME/ls-preview`, or under `~/.local/state`.
+pub fn default_state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// `$XDG_CONFIG_HOME/ls-preview`, or under `~/.config`.
+pub fn default_config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `ls-preview` under the directory in `variable`, or under `fallback` in the
+/// home directory. Relative paths in `variable` are ignored, as the spec says.
+fn xdg_dir(variable: &str, fallback: &str) -> Option<PathBuf> {
+    let base = std::env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))?;
+    Some(base.join("ls-preview"))
+}
+
+/// A file in `dir` about a directory, keyed by its device and inode so it
+/// follows the directory when it's renamed.
+pub(crate) struct KeyedFile {
+    path: PathBuf,
+    header: String,
+}
+
+impl KeyedFile {
+    /// `kind` tells files of different kinds apart in their header.
+    pub(crate) fn new(dir: &Path, kind: &str, metadata: &fs::Metadata) -> Self {
+        KeyedFile {
+            path: dir.join(format!("{:x}-{:x}", metadata.dev(), metadata.ino())),
+            header: format!("ls-preview {kind} {VERSION}\n"),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The contents after the header, `None` if missing or of another kind or version.
+    pub(crate) fn read(&self) -> Option<Vec<u8>> {
+        let mut contents = fs::read(&self.path).ok()?;
+        if !contents.starts_with(self.header.as_bytes()) {
+            return None;
+        }
+        contents.drain(..self.header.len());
+        Some(contents)
+    }
+
+    /// Write the header and then the contents, replacing the old file atomically.
+    pub(crate) fn write(
+        &self,
+        contents: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+    ) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temp_path = self
+            .path
+            .with_extension(format!("tmp{}", std::process::id()));
+        let mut file = io::BufWriter::new(fs::File::create(&temp_path)?);
+        file.write_all(self.header.as_bytes())?;
+        contents(&mut file)?;
+        file.flush()?;
+        drop(file);
+        fs::rename(&temp_path, &self.path)
+    }
+}
@@ -0,0 +1,408 @@
+use console::{Color, Style};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::sync::OnceLock;
+
+use crate::{Entry, FileType, GitStatus, LinkTarget};
+
+#[allow(clippy::if_same_then_else)]
+pub fn get_color_and_indicator(file_type: &FileType) -> (Style, &'static str) {
+    if file_type.is_dir() {
+        (Style::new().blue().bold(), "/")
+    } else if file_type.is_symlink() {
+        (Style::new().magenta(), "@")
+    } else if file_type.is_socket() {
+        (Style::new().magenta(), "=")
+    } else if file_type.is_fifo() {
+        (Style::new().yellow(), "|")
+    } else if file_type.is_block_device() {
+        (Style::new().blue().on_cyan(), "")
+    } else if file_type.is_char_device() {
+        (Style::new().blue().on_yellow(), "")
+    } else if file_type.is_file() {
+        (Style::new(), "")
+    } else {
+        (Style::new(), "")
+    }
+}
+
+/// Style and indicator for an entry by its type and, when its metadata was
+/// fetched, its mode bits, like `ls -F` with the `dircolors` defaults.
+///
+/// Entries the current user can't read, or enter for directories, are dimmed.
+pub fn get_entry_color_and_indicator(entry: &Entry) -> (Style, &'static str) {
+    let (style, indicator) = match (&entry.link_target, &entry.metadata) {
+        (Some(LinkTarget::Broken), _) => (Style::new().red(), "@"),
+        (Some(LinkTarget::Loop), _) => (Style::new().red().reverse(), "@"),
+        (_, Some(metadata)) if !entry.file_type.is_symlink() => {
+            let mode = metadata.permissions().mode();
+            let has = |bits: u32| mode & bits == bits;
+            let (default_style, indicator) = get_color_and_indicator(&entry.file_type);
+            if entry.file_type.is_dir() {
+                let style = if has(S_ISVTX | S_IWOTH) {
+                    Style::new().black().on_green()
+                } else if has(S_IWOTH) {
+                    Style::new().blue().on_green()
+                } else if has(S_ISVTX) {
+                    Style::new().white().on_blue()
+                } else {
+                    default_style
+                };
+                (style, indicator)
+            } else if entry.file_type.is_file() && has(S_ISUID) {
+                (Style::new().white().on_red(), executable_indicator(mode))
+            } else if entry.file_type.is_file() && has(S_ISGID) {
+                (Style::new().black().on_yellow(), executable_indicator(mode))
+            } else if entry.file_type.is_file() && mode & S_IXUGO != 0 {
+                (Style::new().green().bold(), "*")
+            } else {
+                (default_style, indicator)
+            }
+        }
+        _ => get_color_and_indicator(&entry.file_type),
+    };
+    if is_inaccessible(entry) {
+        (style.dim(), indicator)
+    } else {
+        (style, indicator)
+    }
+}
+
+fn executable_indicator(mode: u32) -> &'static str {
+    if mode & S_IXUGO != 0 {
+        "*"
+    } else {
+        ""
+    }
+}
+
+/// Whether the current user can't read the entry, or can't enter a directory,
+/// judged by its mode bits. `false` without metadata.
+pub(crate) fn is_inaccessible(entry: &Entry) -> bool {
+    let Some(metadata) = &entry.metadata else {
+        return false;
+    };
+    if entry.file_type.is_symlink() {
+        return false;
+    }
+    let user = current_user();
+    if user.uid == 0 {
+        // Root can read anything and enter any directory
+        return false;
+    }
+    let mode = metadata.permissions().mode();
+    // The owner's bits apply to the owner even if the group's or other's would allow more
+    let shift = if metadata.uid() == user.uid {
+        6
+    } else if user.gids.contains(&metadata.gid()) {
+        3
+    } else {
+        0
+    };
+    let needed = if entry.file_type.is_dir() { 0o5 } else { 0o4 };
+    (mode >> shift) & needed != needed
+}
+
+struct User {
+    uid: u32,
+    gids: Vec<u32>,
+}
+
+fn current_user() -> &'static User {
+    static USER: OnceLock<User> = OnceLock::new();
+    USER.get_or_init(|| {
+        let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+        let mut gids = vec![0; count.max(0) as usize];
+        let count = unsafe { libc::getgroups(gids.len() as libc::c_int, gids.as_mut_ptr()) };
+        gids.truncate(count.max(0) as usize);
+        gids.push(unsafe { libc::getegid() });
+        User {
+            uid: unsafe { libc::geteuid() },
+            gids,
+        }
+    })
+}
+
+/// Marker shown before the name of an entry with the given status.
+pub fn get_git_marker(status: GitStatus) -> (Style, &'static str) {
+    match status {
+        GitStatus::Conflicted => (Style::new().red().bold(), "U"),
+        GitStatus::Modified => (Style::new().yellow(), "M"),
+        GitStatus::Staged => (Style::new().green(), "+"),
+        GitStatus::Deleted => (Style::new().red(), "D"),
+        GitStatus::Added => (Style::new().green(), "A"),
+        GitStatus::Untracked => (Style::new().red(), "?"),
+        GitStatus::Ignored => (Style::new().dim(), "!"),
+    }
+}
+
+/// Marker shown before the name of a new entry when colors are off, since
+/// otherwise only its style tells it apart.
+pub(crate) fn new_marker(entry: &Entry) -> Option<&'static str> {
+    (entry.is_new && !console::colors_enabled()).then_some("*")
+}
+
+/// Styles parsed from the `LS_COLORS` environment variable, as set by `dircolors`.
+#[derive(Debug, Clone, Default)]
+pub struct LsColors {
+    /// Styles for the two-letter type keys, e.g. `di` or `ex`.
+    types: HashMap<String, Style>,
+    /// Styles for `*suffix` globs, in the order given.
+    globs: Vec<(String, Style)>,
+    /// `ln=target`: style symlinks as the file they point to.
+    link_as_target: bool,
+}
+
+const S_ISUID: u32 = 0o4000;
+const S_ISGID: u32 = 0o2000;
+const S_ISVTX: u32 = 0o1000;
+const S_IWOTH: u32 = 0o0002;
+const S_IXUGO: u32 = 0o0111;
+
+/// Keys whose styling depends on more than the file type.
+const METADATA_KEYS: &[&str] = &["ex", "su", "sg", "tw", "ow", "st", "or", "mi"];
+
+impl LsColors {
+    /// Read `LS_COLORS`, `None` if unset or empty.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var("LS_COLORS").ok()?;
+        if value.is_empty() {
+            return None;
+        }
+        Some(Self::parse(&value))
+    }
+
+    pub fn parse(value: &str) -> Self {
+        let mut colors = LsColors::default();
+        for item in value.split(':') {
+            let Some((key, codes)) = item.split_once('=') else {
+                continue;
+            };
+            if let Some(suffix) = key.strip_prefix('*') {
+                colors.globs.push((suffix.to_lowercase(), parse_sgr(codes)));
+            } else if key == "ln" && codes == "target" {
+                colors.link_as_target = true;
+            } else {
+                colors.types.insert(key.to_string(), parse_sgr(codes));
+            }
+        }
+        colors
+    }
+
+    /// Whether styling needs the entry's mode bits or symlink target,
+    /// see [`Preview::metadata`](crate::Preview::metadata).
+    pub fn needs_metadata(&self) -> bool {
+        self.link_as_target
+            || METADATA_KEYS
+                .iter()
+                .any(|key| self.types.contains_key(*key))
+    }
+
+    /// Style for an entry, `None` if `LS_COLORS` doesn't say.
+    pub fn style(&self, entry: &Entry) -> Option<Style> {
+        if entry.file_type.is_symlink() {
+            return match &entry.link_target {
+                Some(LinkTarget::Broken | LinkTarget::Loop) => {
+                    self.key("or").or_else(|| self.key("ln"))
+                }
+                Some(LinkTarget::Found(target)) if self.link_as_target => self.style_with_mode(
+                    &entry.name,
+                    &target.file_type().into(),
+                    Some(target.permissions().mode()),
+                ),
+                _ => self.key("ln"),
+            };
+        }
+        let mode = entry
+            .metadata
+            .as_ref()
+            .map(|metadata| metadata.permissions().mode());
+        self.style_with_mode(&entry.name, &entry.file_type, mode)
+    }
+
+    fn style_with_mode(
+        &self,
+        name: &OsStr,
+        file_type: &FileType,
+        mode: Option<u32>,
+    ) -> Option<Style> {
+        let mode = mode.unwrap_or(0);
+        let has = |bits: u32| mode & bits == bits;
+        if file_type.is_dir() {
+            if has(S_ISVTX | S_IWOTH) {
+                self.key("tw")
+            } else if has(S_IWOTH) {
+                self.key("ow")
+            } else if has(S_ISVTX) {
+                self.key("st")
+            } else {
+                None
+            }
+            .or_else(|| self.key("di"))
+        } else if file_type.is_fifo() {
+            self.key("pi")
+        } else if file_type.is_socket() {
+            self.key("so")
+        } else if file_type.is_block_device() {
+            self.key("bd")
+        } else if file_type.is_char_device() {
+            self.key("cd")
+        } else if file_type.is_file() {
+            if has(S_ISUID) {
+                self.key("su")
+            } else if has(S_ISGID) {
+                self.key("sg")
+            } else if mode & S_IXUGO != 0 {
+                self.key("ex")
+            } else {
+                None
+            }
+            .or_else(|| self.glob(name))
+            .or_else(|| self.key("fi"))
+        } else {
+            self.key("no")
+        }
+    }
+
+    fn key(&self, key: &str) -> Option<Style> {
+        self.types.get(key).cloned()
+    }
+
+    /// Later globs take precedence, matching is case-insensitive.
+    fn glob(&self, name: &OsStr) -> Option<Style> {
+        let name = name.to_string_lossy().to_lowercase();
+        self.globs
+            .iter()
+            .rev()
+            .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .map(|(_, style)| style.clone())
+    }
+}
+
+/// Convert SGR parameters such as `01;38;5;208` into a `Style`.
+fn parse_sgr(codes: &str) -> Style {
+    let mut style = Style::new();
+    let mut codes = codes.split(';').map(|code| code.parse::<u8>().unwrap_or(0));
+    while let Some(code) = codes.next() {
+        style = match code {
+            1 => style.bold(),
+            2 => style.dim(),
+            3 => style.italic(),
+            4 => style.underlined(),
+            5 => style.blink(),
+            6 => style.blink_fast(),
+            7 => style.reverse(),
+            8 => style.hidden(),
+            9 => style.strikethrough(),
+            30..=37 => style.fg(basic_color(code - 30)),
+            40..=47 => style.bg(basic_color(code - 40)),
+            90..=97 => style.fg(basic_color(code - 90)).bright(),
+            100..=107 => style.bg(basic_color(code - 100)).on_bright(),
+            38 => match extended_color(&mut codes) {
+                Some(color) => style.fg(color),
+                None => style,
+            },
+            48 => match extended_color(&mut codes) {
+                Some(color) => style.bg(color),
+                None => style,
+            },
+            _ => style,
+        };
+    }
+    style
+}
+
+fn basic_color(index: u8) -> Color {
+    match index {
+        0 => Color::Black,
+        1 => Color::Red,
+        2 => Color::Green,
+        3 => Color::Yellow,
+        4 => Color::Blue,
+        5 => Color::Magenta,
+        6 => Color::Cyan,
+        _ => Color::White,
+    }
+}
+
+/// `5;n` for the 256-color palette or `2;r;g;b`, approximated to the 6x6x6 cube.
+fn extended_color(codes: &mut impl Iterator<Item = u8>) -> Option<Color> {
+    match codes.next()? {
+        5 => Some(Color::Color256(codes.next()?)),
+        2 => {
+            let mut channel = || codes.next().map(|c| (c as u16 * 5 + 127) / 255);
+            let (r, g, b) = (channel()?, channel()?, channel()?);
+            Some(Color::Color256((16 + 36 * r + 6 * g + b) as u8))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_style(colors: &LsColors, name: &str, mode: u32) -> Option<Style> {
+        colors.style_with_mode(OsStr::new(name), &FileType::File, Some(mode))
+    }
+
+    #[test]
+    fn later_globs_take_precedence() {
+        let colors = LsColors::parse("*.gz=31:*.tar.gz=32:*.GZ=33");
+        assert_eq!(
+            file_style(&colors, "a.tar.gz", 0o644),
+            Some(Style::new().yellow())
+        );
+        let colors = LsColors::parse("*.tar.gz=32:*.gz=31");
+        assert_eq!(
+            file_style(&colors, "A.TAR.GZ", 0o644),
+            Some(Style::new().red())
+        );
+    }
+
+    #[test]
+    fn globs_fall_back_to_fi_and_yield_to_ex() {
+        let colors = LsColors::parse("fi=37:ex=01;32:*.sh=36");
+        assert_eq!(
+            file_style(&colors, "run.sh", 0o755),
+            Some(Style::new().bold().green())
+        );
+        assert_eq!(
+            file_style(&colors, "run.sh", 0o644),
+            Some(Style::new().cyan())
+        );
+        assert_eq!(
+            file_style(&colors, "notes.txt", 0o644),
+            Some(Style::new().white())
+        );
+    }
+
+    #[test]
+    fn extended_colors() {
+        assert_eq!(parse_sgr("38;5;208"), Style::new().fg(Color::Color256(208)));
+        assert_eq!(
+            parse_sgr("01;48;5;17"),
+            Style::new().bold().bg(Color::Color256(17))
+        );
+        // Approximated to the 6x6x6 cube
+        assert_eq!(
+            parse_sgr("38;2;255;0;0"),
+            Style::new().fg(Color::Color256(196))
+        );
+        assert_eq!(
+            parse_sgr("38;2;0;128;255"),
+            Style::new().fg(Color::Color256(16 + 6 * 3 + 5))
+        );
+        // Truncated sequences are ignored
+        assert_eq!(parse_sgr("38;5"), Style::new());
+    }
+
+    #[test]
+    fn basic_and_bright_colors() {
+        assert_eq!(parse_sgr("01;34"), Style::new().bold().blue());
+        assert_eq!(parse_sgr("92"), Style::new().green().bright());
+        assert_eq!(parse_sgr("30;43"), Style::new().black().on_yellow());
+        assert_eq!(parse_sgr(""), Style::new());
+    }
+}
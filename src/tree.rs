@@ -0,0 +1,175 @@
+use std::io::{self, Write};
+use std::time::Instant;
+
+use crate::style::new_marker;
+use crate::{Preview, Renderer};
+
+const BRANCH: &str = "├─ ";
+const LAST_BRANCH: &str = "└─ ";
+const CONTINUATION: &str = "│  ";
+const LAST_CONTINUATION: &str = "   ";
+
+/// Render `preview` with up to `depth` levels of subdirectories nested under
+/// it and return the number of lines written.
+///
+/// The preview's `max_lines` is shared by all levels: a directory keeps half
+/// of its lines for itself and the rest are split between its subdirectories,
+/// each shown on its own branch. Every level picks its entries like a plain
+/// preview, so a crowded subdirectory only shows its own subdirectories.
+///
+/// The time limit is for the whole tree, and a single `git status` marks
+/// the entries of every level. A level with nested ones waits for it only
+/// for half of what's left, so a slow `git status` costs the markers rather
+/// than the nested listings.
+pub fn render_tree<W: Write>(
+    out: &mut W,
+    renderer: &Renderer,
+    preview: &Preview,
+    depth: usize,
+) -> io::Result<usize> {
+    let deadline = preview.deadline();
+    let preview = Preview {
+        shared_status: preview.pending_status(),
+        ..preview.clone()
+    };
+    let lines = tree_lines(renderer, &preview, depth, deadline)?;
+    for line in &lines {
+        writeln!(out, "{line}")?;
+    }
+    Ok(lines.len())
+}
+
+fn tree_lines(
+    renderer: &Renderer,
+    preview: &Preview,
+    depth: usize,
+    deadline: Option<Instant>,
+) -> io::Result<Vec<String>> {
+    let own_lines = if depth == 0 {
+        preview.max_lines
+    } else {
+        preview.max_lines.div_ceil(2)
+    };
+    let status_deadline = match deadline {
+        Some(deadline) if depth > 0 => {
+            let now = Instant::now();
+            Some(now + deadline.saturating_duration_since(now) / 2)
+        }
+        _ => deadline,
+    };
+    let listing = preview
+        .clone()
+        .max_lines(own_lines)
+        .run_until(deadline, status_deadline)?;
+
+    let mut buffer = vec![];
+    renderer.render(&mut buffer, &listing)?;
+    let mut lines: Vec<String> = String::from_utf8_lossy(&buffer)
+        .lines()
+        .map(str::to_string)
+        .collect();
+    if depth == 0 {
+        return Ok(lines);
+    }
+
+    let mut remaining_lines = preview.max_lines.saturating_sub(lines.len());
+    let subdirectories: Vec<_> = listing
+        .entries
+        .iter()
+        .filter(|entry| entry.file_type.is_dir())
+        .take(remaining_lines)
+        .collect();
+    for (i, entry) in subdirectories.iter().enumerate() {
+        let is_last = i == subdirectories.len() - 1;
+        let (branch, continuation) = if is_last {
+            (LAST_BRANCH, LAST_CONTINUATION)
+        } else {
+            (BRANCH, CONTINUATION)
+        };
+
+        let (style, indicator) = renderer.style(entry);
+        let new = new_marker(entry)
+            .map(|marker| format!("{marker} "))
+            .unwrap_or_default();
+        let icon = entry
+            .icon
+            .map(|icon| format!("{} ", style.apply_to(icon)))
+            .unwrap_or_default();
+        let label = format!(
+            "{}{}{}{}{} ",
+            branch,
+            new,
+            icon,
+            style.apply_to(entry.display_name()),
+            indicator
+        );
+        let label_width = console::measure_text_width(&label);
+
+        // Split what's left evenly, so lines unused by earlier subdirectories go to later ones
+        let share = (remaining_lines / (subdirectories.len() - i)).max(1);
+        // Unreadable subdirectories are shown without contents
+        let child_lines = preview
+            .source
+            .as_ref()
+            .map(|source| source.open(&entry.name))
+            .transpose()
+            .and_then(|source| {
+                let child = Preview {
+                    directory: listing.directory.join(&entry.name),
+                    source,
+                    max_lines: share,
+                    width: listing
+                        .width
+                        .map(|width| width.saturating_sub(label_width as u16)),
+                    ..preview.clone()
+                };
+                tree_lines(renderer, &child, depth - 1, deadline)
+            })
+            .unwrap_or_default();
+
+        if child_lines.is_empty() {
+            lines.push(label.trim_end().to_string());
+        }
+        let indent = " ".repeat(label_width - continuation.chars().count());
+        for (j, child_line) in child_lines.iter().enumerate() {
+            if j == 0 {
+                lines.push(format!("{label}{child_line}"));
+            } else {
+                lines.push(format!("{continuation}{indent}{child_line}"));
+            }
+        }
+        remaining_lines = remaining_lines.saturating_sub(child_lines.len().max(1));
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::git::PendingStatus;
+    use crate::{FileType, MemorySource};
+
+    #[test]
+    fn slow_git_status_leaves_time_for_nested_levels() {
+        let src = MemorySource::new([("lib.rs", FileType::File), ("main.rs", FileType::File)]);
+        let source = MemorySource::new([("README.md", FileType::File)]).subdirectory("src", src);
+        let (pending, _sender) = PendingStatus::never(Path::new("root"));
+        let preview = Preview {
+            shared_status: Some(Arc::new(pending)),
+            ..Preview::new("root")
+                .source(Arc::new(source))
+                .width(Some(40))
+                .max_lines(4)
+                .git_status(true)
+                .time_limit(Some(Duration::from_millis(200)))
+        };
+        let mut out = vec![];
+        render_tree(&mut out, &Renderer::new(), &preview, 1).unwrap();
+        let out = console::strip_ansi_codes(&String::from_utf8(out).unwrap()).into_owned();
+        assert_eq!(out, "README.md  src/\n└─ src/ lib.rs  main.rs\n");
+    }
+}
@@ -0,0 +1,105 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const ELLIPSIS: &str = "…";
+
+/// Shorten `name` to at most `max_width` columns by replacing its middle with
+/// `…`, keeping the extension. `None` if it already fits.
+///
+/// Only cuts between grapheme clusters, so wide CJK characters and emoji
+/// sequences are kept whole or dropped whole.
+pub(crate) fn truncate_middle(name: &str, max_width: u16) -> Option<String> {
+    let max_width = max_width as usize;
+    if name.width() <= max_width {
+        return None;
+    }
+    if max_width <= ELLIPSIS.width() {
+        return Some(ELLIPSIS.to_string());
+    }
+
+    let (stem, extension) = match name.rfind('.') {
+        // Keep the extension when at least a few characters of the stem fit too
+        Some(dot) if dot > 0 && name[dot..].width() + ELLIPSIS.width() + 2 <= max_width => {
+            name.split_at(dot)
+        }
+        _ => (name, ""),
+    };
+    let budget = max_width - ELLIPSIS.width() - extension.width();
+    // Most of the budget goes to the start, it's what the name is recognized by
+    let tail_budget = budget / 3;
+    let head = take_width(stem.graphemes(true), budget - tail_budget).concat();
+    let mut tail = take_width(stem.graphemes(true).rev(), tail_budget);
+    tail.reverse();
+    Some(format!("{head}{ELLIPSIS}{}{extension}", tail.concat()))
+}
+
+/// Graphemes from `graphemes` while they fit in `max_width`, in the order taken.
+fn take_width<'a>(graphemes: impl Iterator<Item = &'a str>, max_width: usize) -> Vec<&'a str> {
+    let mut width = 0;
+    let mut taken = vec![];
+    for grapheme in graphemes {
+        width += grapheme.width();
+        if width > max_width {
+            break;
+        }
+        taken.push(grapheme);
+    }
+    taken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every grapheme of `shortened` apart from the ellipsis comes whole from `name`.
+    fn assert_whole_graphemes(name: &str, shortened: &str) {
+        let graphemes: Vec<&str> = name.graphemes(true).collect();
+        for grapheme in shortened.graphemes(true).filter(|&g| g != ELLIPSIS) {
+            assert!(graphemes.contains(&grapheme), "{grapheme:?} was cut");
+        }
+    }
+
+    #[test]
+    fn fitting_names_are_kept() {
+        assert_eq!(truncate_middle("report.pdf", 10), None);
+        assert_eq!(truncate_middle("報告.pdf", 8), None);
+    }
+
+    #[test]
+    fn keeps_extension_and_gives_the_head_most_room() {
+        assert_eq!(
+            truncate_middle("very_long_report_name.pdf", 20).as_deref(),
+            Some("very_long_…_name.pdf")
+        );
+        // No room for the extension and some of the stem
+        assert_eq!(truncate_middle("report.pdf", 6).as_deref(), Some("repo…f"));
+        assert_eq!(truncate_middle("report.pdf", 1).as_deref(), Some("…"));
+    }
+
+    #[test]
+    fn wide_characters_are_not_split() {
+        let name = "年度報告書の最終版です.pdf";
+        for max_width in 8..name.width() as u16 {
+            let shortened = truncate_middle(name, max_width).unwrap();
+            assert!(shortened.width() <= max_width as usize, "{shortened}");
+            assert!(shortened.ends_with(".pdf"), "{shortened}");
+            assert_whole_graphemes(name, &shortened);
+        }
+        // An odd budget leaves a column unused rather than half a character
+        assert_eq!(
+            truncate_middle("年度報告書の最終版です.pdf", 12).as_deref(),
+            Some("年度…す.pdf")
+        );
+    }
+
+    #[test]
+    fn emoji_sequences_are_not_split() {
+        let name = "👩‍👩‍👧‍👦family👨‍👩‍👧photos🏳️‍🌈.pdf";
+        for max_width in 8..name.width() as u16 {
+            let shortened = truncate_middle(name, max_width).unwrap();
+            assert!(shortened.width() <= max_width as usize, "{shortened}");
+            assert!(shortened.ends_with(".pdf"), "{shortened}");
+            assert_whole_graphemes(name, &shortened);
+        }
+    }
+}
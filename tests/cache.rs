@@ -0,0 +1,43 @@
+use std::fs;
+use std::path::PathBuf;
+
+use ls_preview::{refresh_cache, Preview};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ls-preview-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn cached_directories_are_listed_completely_and_in_order() {
+    let root = temp_dir("cache");
+    let directory = root.join("huge");
+    // Created in reverse, so directory order is unlikely to be sorted
+    for i in (0..500).rev() {
+        fs::create_dir_all(directory.join(format!("dir{i:03}"))).unwrap();
+    }
+    let cache_dir = root.join("cache");
+    refresh_cache(&cache_dir, &directory).unwrap();
+
+    let listing = Preview::new(&directory)
+        .width(Some(80))
+        .max_lines(2)
+        .time_limit(None)
+        .cache_dir(Some(cache_dir))
+        .run()
+        .unwrap();
+    assert!(listing.cached);
+    assert!(listing.complete);
+    assert_eq!(listing.entries.len(), 500);
+    assert_eq!(listing.shown() + listing.omitted(), 500);
+    let shown: Vec<_> = listing.entries[..listing.shown()]
+        .iter()
+        .map(|entry| entry.name.to_string_lossy().into_owned())
+        .collect();
+    let expected: Vec<_> = (0..listing.shown()).map(|i| format!("dir{i:03}")).collect();
+    assert_eq!(shown, expected);
+
+    fs::remove_dir_all(root).unwrap();
+}
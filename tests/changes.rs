@@ -0,0 +1,31 @@
+use std::fs;
+
+use ls_preview::Preview;
+
+#[test]
+fn new_entries_are_marked_without_colors() {
+    let root = std::env::temp_dir().join(format!("ls-preview-changes-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let directory = root.join("downloads");
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("old.txt"), "").unwrap();
+    fs::write(directory.join("gone.txt"), "").unwrap();
+    let preview = Preview::new(&directory)
+        .width(Some(80))
+        .time_limit(None)
+        .state_dir(Some(root.join("state")));
+    preview.run().unwrap();
+
+    fs::remove_file(directory.join("gone.txt")).unwrap();
+    fs::write(directory.join("new.txt"), "").unwrap();
+    console::set_colors_enabled(false);
+    let listing = preview.run().unwrap();
+    let mut buffer = vec![];
+    ls_preview::render(&mut buffer, &listing).unwrap();
+    assert_eq!(
+        String::from_utf8(buffer).unwrap(),
+        "* new.txt  old.txt  −1 removed\n"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use ls_preview::{FileType, Listing, MemorySource, Preview};
+
+fn preview(entries: impl IntoIterator<Item = (String, FileType)>) -> Listing {
+    Preview::new("synthetic")
+        .source(Arc::new(MemorySource::new(entries)))
+        .width(Some(80))
+        .max_lines(2)
+        .time_limit(None)
+        .run()
+        .unwrap()
+}
+
+fn files(count: usize) -> impl Iterator<Item = (String, FileType)> {
+    (0..count).map(|i| (format!("file{i}"), FileType::File))
+}
+
+fn dirs(count: usize) -> impl Iterator<Item = (String, FileType)> {
+    (0..count).map(|i| (format!("dir{i}"), FileType::Dir))
+}
+
+fn rendered(listing: &Listing) -> String {
+    let mut buffer = vec![];
+    ls_preview::render(&mut buffer, listing).unwrap();
+    console::strip_ansi_codes(&String::from_utf8(buffer).unwrap()).into_owned()
+}
+
+#[test]
+fn too_many_files_falls_back_to_directories() {
+    let listing = preview(files(100_000).chain(dirs(3)));
+    assert!(listing.dirs_only);
+    assert!(listing.complete);
+    assert!(!listing.timed_out);
+    assert_eq!(listing.entries.len(), 3);
+    assert_eq!(listing.dropped.len(), 100_000);
+    assert_eq!(listing.shown(), 3);
+    assert_eq!(listing.omitted(), 100_000);
+    assert_eq!(rendered(&listing), "dir0/  dir1/  dir2/  … +100000 files\n");
+}
+
+#[test]
+fn listing_stops_once_directories_fill_the_lines() {
+    let listing = preview(dirs(100_000));
+    assert!(listing.dirs_only);
+    assert!(!listing.complete);
+    assert!(!listing.timed_out);
+    assert!(listing.dropped.is_empty());
+    assert_eq!(listing.lines(), 2);
+    assert!(listing.has_footer());
+    assert_eq!(listing.shown(), 19);
+    assert_eq!(listing.omitted(), 1);
+    assert_eq!(
+        rendered(&listing),
+        "dir0/   dir1/   dir10/  dir11/  dir12/  dir13/  dir14/  dir15/  dir16/  dir17/\n\
+         dir18/  dir19/  dir2/   dir3/   dir4/   dir5/   dir6/   dir7/   dir8/   … +1+\n"
+    );
+}
+
+#[test]
+fn hidden_entries_are_counted_in_the_footer() {
+    let dotfiles = (0..10_000).map(|i| (format!(".hidden{i}"), FileType::File));
+    let listing = preview(files(90_000).chain(dotfiles).chain(dirs(1)));
+    assert_eq!(listing.hidden, 10_000);
+    assert_eq!(listing.dropped.len(), 90_000);
+    assert_eq!(listing.shown(), 1);
+    assert_eq!(rendered(&listing), "dir0/  … +90000 files, 10000 hidden\n");
+}
+
+#[test]
+fn listing_is_the_same_every_time() {
+    let names = |listing: &Listing| -> Vec<_> {
+        listing.entries[..listing.shown()]
+            .iter()
+            .map(|entry| entry.name.clone())
+            .collect()
+    };
+    let first = preview(files(100_000).chain(dirs(50)));
+    for _ in 0..3 {
+        let again = preview(files(100_000).chain(dirs(50)));
+        assert_eq!(names(&again), names(&first));
+        assert_eq!(again.omitted(), first.omitted());
+    }
+}
+
+#[test]
+fn entries_that_fit_are_all_shown() {
+    let listing = preview(files(4).chain(dirs(2)));
+    assert!(!listing.dirs_only);
+    assert!(!listing.has_footer());
+    assert_eq!(listing.shown(), 6);
+    assert_eq!(
+        rendered(&listing),
+        "dir0/  dir1/  file0  file1  file2  file3\n"
+    );
+}
+
+#[test]
+fn ignore_files_on_disk_are_not_applied() {
+    let directory = std::env::temp_dir().join(format!("ls-preview-ignore-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join(".ignore"), "*.log\n").unwrap();
+
+    let listing = Preview::new(&directory)
+        .source(Arc::new(MemorySource::new(
+            files(3).chain([("debug.log".to_string(), FileType::File)]),
+        )))
+        .respect_ignore(true)
+        .time_limit(None)
+        .run()
+        .unwrap();
+    assert_eq!(listing.entries.len(), 4);
+    assert_eq!(listing.hidden, 0);
+
+    std::fs::remove_dir_all(directory).unwrap();
+}